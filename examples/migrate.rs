@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+use webbase::migrate::{cli_main, register_migration};
+
+/// 迁移命令行，服务复制本文件到src/bin/migrate.rs，将示例迁移替换为服务自己的迁移
+/// - `DATABASE_URL=mysql://root@127.0.0.1/test cargo run --example migrate --features migrate -- status`
+/// - 命令: `up [n]`、`down [n]`、`status`、`fresh`
+#[derive(DeriveMigrationName)]
+struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Alias::new("migrate_example"))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Alias::new("id"))
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Alias::new("name")).string().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(Alias::new("migrate_example"))
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    register_migration(|| Box::new(Migration));
+    cli_main().await
+}
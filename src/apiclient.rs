@@ -0,0 +1,327 @@
+use crate::ClientRsp;
+use reqwest::{Method, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::Instrument;
+
+/// ApiClient调用失败的错误类型
+#[derive(Debug)]
+pub enum ApiError {
+    /// 请求发送失败，如连接失败或超时
+    Request(reqwest::Error),
+    /// 服务端返回非2xx的HTTP状态码
+    Status(StatusCode),
+    /// 返回数据无法解析为ClientRsp或目标类型
+    Decode(String),
+    /// ClientRsp.status为false，message为服务端给出的错误原因
+    Remote { code: u16, message: String },
+}
+
+impl Error for ApiError {}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Request(e) => write!(f, "request error: {}", e),
+            ApiError::Status(status) => write!(f, "http status error: {}", status),
+            ApiError::Decode(reason) => write!(f, "response data decoding error: {}", reason),
+            ApiError::Remote { code, message } => {
+                write!(f, "error reason: {} (code {})", message, code)
+            }
+        }
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        ApiError::Request(e)
+    }
+}
+
+impl ApiError {
+    /// 连接失败、超时以及5xx错误可以重试，其他错误重试也不会有不同结果
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ApiError::Request(e) => e.is_connect() || e.is_timeout(),
+            ApiError::Status(status) => status.is_server_error(),
+            _ => false,
+        }
+    }
+}
+
+/// with_retries允许的最大重试次数
+pub const API_MAX_RETRIES: u32 = 10;
+pub const API_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+pub const API_TIMEOUT: Duration = Duration::from_secs(5);
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::<reqwest::Client>::new();
+
+/// 进程内共享的reqwest::Client，所有ApiClient默认复用其连接池
+pub fn get_http_client() -> &'static reqwest::Client {
+    HTTP_CLIENT.get_or_init(|| build_http_client(API_CONNECT_TIMEOUT, API_TIMEOUT))
+}
+
+fn build_http_client(connect_timeout: Duration, timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .http1_only()
+        .connect_timeout(connect_timeout)
+        .timeout(timeout)
+        .build()
+        .unwrap()
+}
+
+/// 调用返回ClientRsp格式数据的服务的通用客户端
+#[derive(Clone, Debug)]
+pub struct ApiClient {
+    pub base_url: String,        // 服务地址，不带末尾的'/'
+    pub client: reqwest::Client, // clone只是增加引用计数，连接池共享
+    pub retries: u32,            // 可重试错误的最大重试次数
+    pub retry_interval: Duration,
+}
+
+impl ApiClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: get_http_client().clone(),
+            retries: 0,
+            retry_interval: Duration::from_millis(200),
+        }
+    }
+
+    /// 使用独立的超时设置，会新建一个不与其他ApiClient共享的连接池
+    pub fn with_timeout(mut self, connect_timeout: Duration, timeout: Duration) -> Self {
+        self.client = build_http_client(connect_timeout, timeout);
+        self
+    }
+
+    /// 第n次重试前等待retry_interval * n，retries超过API_MAX_RETRIES时按API_MAX_RETRIES处理
+    pub fn with_retries(mut self, retries: u32, retry_interval: Duration) -> Self {
+        self.retries = retries.min(API_MAX_RETRIES);
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ApiError> {
+        self.call::<(), T>(Method::GET, path, None).await
+    }
+
+    pub async fn post<D: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        data: &D,
+    ) -> Result<T, ApiError> {
+        self.call(Method::POST, path, Some(data)).await
+    }
+
+    /// 只检查HTTP状态码，不解析返回内容，用于health类接口
+    pub async fn check(&self, path: &str) -> Result<(), ApiError> {
+        let url = self.url(path);
+        let span = tracing::info_span!("api_call", method = "GET", url = url.as_str());
+        async {
+            self.send_with_retry::<()>(&Method::GET, &url, None)
+                .await
+                .map(|_| ())
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// 发送请求并把ClientRsp.message解析为T，status为false时message作为错误原因返回
+    pub async fn call<D: Serialize, T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        data: Option<&D>,
+    ) -> Result<T, ApiError> {
+        let url = self.url(path);
+        let span = tracing::info_span!("api_call", method = method.as_str(), url = url.as_str());
+        async {
+            let response = self.send_with_retry(&method, &url, data).await?;
+            let resp = response
+                .json::<ClientRsp>()
+                .await
+                .map_err(|e| ApiError::Decode(e.to_string()))?;
+            decode_client_rsp(resp)
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn send_with_retry<D: Serialize>(
+        &self,
+        method: &Method,
+        url: &str,
+        data: Option<&D>,
+    ) -> Result<reqwest::Response, ApiError> {
+        let mut attempt = 0;
+        loop {
+            let mut builder = self.client.request(method.clone(), url);
+            if let Some(data) = data {
+                builder = builder.json(data);
+            }
+            let err = match builder.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    // 服务端开启了HTTP状态码模式时，错误状态码下仍然带有ClientRsp格式的错误原因
+                    let status = response.status();
+                    match response.json::<ClientRsp>().await {
+                        Ok(resp) if !resp.status => return Err(remote_error(resp)),
+                        _ => ApiError::Status(status),
+                    }
+                }
+                Err(e) => ApiError::from(e),
+            };
+            if attempt >= self.retries || !err.is_retryable() {
+                tracing::error!("api call failed: {}", err);
+                return Err(err);
+            }
+            attempt += 1;
+            tracing::warn!(
+                "api call failed: {}, retry {}/{}",
+                err,
+                attempt,
+                self.retries
+            );
+            tokio::time::sleep(self.retry_interval.saturating_mul(attempt)).await;
+        }
+    }
+}
+
+/// 将ClientRsp解析为业务数据，status为false时message为错误原因
+pub fn decode_client_rsp<T: DeserializeOwned>(resp: ClientRsp) -> Result<T, ApiError> {
+    if resp.status {
+        serde_json::from_value::<T>(resp.message).map_err(|e| ApiError::Decode(e.to_string()))
+    } else {
+        Err(remote_error(resp))
+    }
+}
+
+fn remote_error(resp: ClientRsp) -> ApiError {
+    let message = match resp.message {
+        serde_json::Value::String(reason) => reason,
+        other => other.to_string(),
+    };
+    ApiError::Remote {
+        code: resp.code,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod apiclient_tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Data {
+        id: u32,
+    }
+
+    /// 启动本地的测试服务，返回服务地址和/unavailable接口被调用的次数
+    fn start_server() -> (String, Arc<AtomicU32>) {
+        let hits = Arc::new(AtomicU32::new(0));
+        let counter = hits.clone();
+        let server = HttpServer::new(move || {
+            let counter = counter.clone();
+            App::new()
+                .route(
+                    "/ok",
+                    web::get().to(|| async {
+                        HttpResponse::Ok().json(serde_json::json!({
+                            "status": true, "code": 200, "message": { "id": 1 }
+                        }))
+                    }),
+                )
+                .route(
+                    "/text",
+                    web::get().to(|| async { HttpResponse::Ok().body("not json") }),
+                )
+                .route(
+                    "/remote",
+                    web::get().to(|| async {
+                        HttpResponse::BadRequest().json(serde_json::json!({
+                            "status": false, "code": 400, "message": "参数错误"
+                        }))
+                    }),
+                )
+                .route(
+                    "/unavailable",
+                    web::get().to(move || {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        async { HttpResponse::ServiceUnavailable().finish() }
+                    }),
+                )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        (format!("http://{}/", addr), hits)
+    }
+
+    #[actix_web::test]
+    async fn api_client_test() {
+        let (url, hits) = start_server();
+        let client = ApiClient::new(&url).with_retries(2, Duration::from_millis(10));
+        assert_eq!(client.url("/ok"), format!("{}ok", url));
+
+        assert_eq!(client.get::<Data>("/ok").await.unwrap().id, 1);
+        assert!(client.check("/ok").await.is_ok());
+        assert!(matches!(
+            client.get::<String>("/ok").await,
+            Err(ApiError::Decode(_))
+        ));
+        assert!(matches!(
+            client.get::<Data>("/text").await,
+            Err(ApiError::Decode(_))
+        ));
+        match client.get::<Data>("/remote").await {
+            Err(ApiError::Remote { code, message }) => {
+                assert_eq!((code, message.as_str()), (400, "参数错误"))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // 404不重试，503重试后仍失败
+        let err = client.get::<Data>("/missing").await.unwrap_err();
+        assert!(matches!(err, ApiError::Status(StatusCode::NOT_FOUND)));
+        assert!(!err.is_retryable());
+        let err = client.get::<Data>("/unavailable").await.unwrap_err();
+        assert!(matches!(
+            err,
+            ApiError::Status(StatusCode::SERVICE_UNAVAILABLE)
+        ));
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        let client = ApiClient::new(&url).with_retries(u32::MAX, Duration::MAX);
+        assert_eq!(client.retries, API_MAX_RETRIES);
+    }
+
+    #[test]
+    fn decode_client_rsp_test() {
+        let rsp = |status: bool, message: serde_json::Value| ClientRsp {
+            status,
+            code: if status { 200 } else { 500 },
+            message,
+        };
+        let data: Data = decode_client_rsp(rsp(true, serde_json::json!({ "id": 2 }))).unwrap();
+        assert_eq!(data.id, 2);
+        match decode_client_rsp::<Data>(rsp(false, serde_json::json!({ "reason": "x" }))) {
+            Err(ApiError::Remote { code, message }) => {
+                assert_eq!((code, message.as_str()), (500, r#"{"reason":"x"}"#))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use actix_web::{App, HttpServer};
+use webbase::idm_server::{idm_scope, IdmServerConfig};
+use webbase::keycenter_server::KeycenterConfig;
+
+/// 本地开发和测试使用的IDM服务，可配合init_idm使用
+/// - IDM_BIND: 监听地址，默认127.0.0.1:8710
+/// - IDM_USERS_FILE: 用户和应用秘钥文件
+/// - IDM_RBAC_FILES: 逗号分隔的RBAC文件列表，每个文件为一个应用的RpConfig
+/// - IDM_RSA_PRIVATE/IDM_RSA_PUBLIC: RSA私钥和公钥文件路径，设置后使用RS512
+/// - IDM_SECRET: 未设置RSA时使用的HS512秘钥
+#[actix_web::main]
+pub async fn main() -> std::io::Result<()> {
+    let bind = std::env::var("IDM_BIND").unwrap_or("127.0.0.1:8710".into());
+    let users_file = std::env::var("IDM_USERS_FILE").expect("IDM_USERS_FILE required");
+    let rbac_files: Vec<String> = std::env::var("IDM_RBAC_FILES")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|each| !each.trim().is_empty())
+        .map(|each| each.trim().to_string())
+        .collect();
+    let token = match (
+        std::env::var("IDM_RSA_PRIVATE"),
+        std::env::var("IDM_RSA_PUBLIC"),
+    ) {
+        (Ok(private_file), Ok(public_file)) => KeycenterConfig::new_rsa(
+            std::fs::read_to_string(private_file)?,
+            std::fs::read_to_string(public_file)?,
+        ),
+        _ => KeycenterConfig::new_hs512(
+            std::env::var("IDM_SECRET").expect("IDM_SECRET or RSA key files required"),
+        ),
+    };
+    let config = IdmServerConfig::load(users_file, &rbac_files, token)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+
+    println!("idm server listening on {}", bind);
+    HttpServer::new(move || App::new().service(idm_scope(config.clone())))
+        .bind(bind)?
+        .run()
+        .await
+}
@@ -0,0 +1,29 @@
+use actix_web::{App, HttpServer};
+use webbase::keycenter_server::{keycenter_scope, KeycenterConfig};
+
+/// 本地开发和集成测试使用的keycenter服务
+/// - KEYCENTER_BIND: 监听地址，默认127.0.0.1:8700
+/// - KEYCENTER_RSA_PRIVATE/KEYCENTER_RSA_PUBLIC: RSA私钥和公钥文件路径，设置后使用RS512
+/// - KEYCENTER_SECRET: 未设置RSA时使用的HS512秘钥
+#[actix_web::main]
+pub async fn main() -> std::io::Result<()> {
+    let bind = std::env::var("KEYCENTER_BIND").unwrap_or("127.0.0.1:8700".into());
+    let config = match (
+        std::env::var("KEYCENTER_RSA_PRIVATE"),
+        std::env::var("KEYCENTER_RSA_PUBLIC"),
+    ) {
+        (Ok(private_file), Ok(public_file)) => KeycenterConfig::new_rsa(
+            std::fs::read_to_string(private_file)?,
+            std::fs::read_to_string(public_file)?,
+        ),
+        _ => KeycenterConfig::new_hs512(
+            std::env::var("KEYCENTER_SECRET").expect("KEYCENTER_SECRET or RSA key files required"),
+        ),
+    };
+
+    println!("keycenter server listening on {}", bind);
+    HttpServer::new(move || App::new().service(keycenter_scope(config.clone())))
+        .bind(bind)?
+        .run()
+        .await
+}
@@ -0,0 +1,317 @@
+pub mod tiered;
+pub use tiered::TieredCache;
+
+use crate::kv::{KvStore, KvTtl};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 缓存值的序列化方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CacheCodec {
+    #[default]
+    Json,
+    Bincode,
+}
+
+impl CacheCodec {
+    pub fn encode<T: Serialize>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        Ok(match self {
+            CacheCodec::Json => serde_json::to_vec(value)?,
+            CacheCodec::Bincode => bincode::serialize(value)?,
+        })
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, value: &[u8]) -> anyhow::Result<T> {
+        Ok(match self {
+            CacheCodec::Json => serde_json::from_slice(value)?,
+            CacheCodec::Bincode => bincode::deserialize(value)?,
+        })
+    }
+}
+
+/// 带命名空间的类型化缓存，key自动加上`{namespace}:`前缀，值按codec序列化
+/// - `let cache = Cache::kv("order");`
+/// - `cache.set("1", &order, Some(Duration::from_secs(60))).await?;`
+/// - `let order: Option<Order> = cache.get("1").await?;`
+#[derive(Clone)]
+pub struct Cache {
+    pub backend: Arc<dyn KvStore>,
+    pub namespace: String,
+    pub codec: CacheCodec,
+}
+
+impl Cache {
+    pub fn new(backend: Arc<dyn KvStore>, namespace: &str) -> Self {
+        Self {
+            backend,
+            namespace: namespace.to_string(),
+            codec: CacheCodec::default(),
+        }
+    }
+
+    /// 使用init_kv_store设置的KvStore，不依赖具体的redis库
+    pub fn kv(namespace: &str) -> Self {
+        Self::new(crate::kv::get_kv_store().clone(), namespace)
+    }
+
+    /// 使用redisfred::init_redis_pool初始化的连接池
+    #[cfg(feature = "redis-fred")]
+    pub fn fred(namespace: &str) -> Self {
+        Self::new(
+            Arc::new(crate::redisfred::get_redis_pool().clone()),
+            namespace,
+        )
+    }
+
+    /// 使用redis::init_redis_pool初始化的连接
+    #[cfg(feature = "redis-rs")]
+    pub fn redis(namespace: &str) -> Self {
+        Self::new(Arc::new(crate::redis::get_redis_pool().clone()), namespace)
+    }
+
+    /// 以PermissionMap中的app_name作为命名空间，不同业务共用redis时互不影响
+    pub fn for_app(backend: Arc<dyn KvStore>) -> Self {
+        let app_name = crate::permission::try_get_pmap()
+            .map(|pmap| pmap.app_name.clone())
+            .unwrap_or_default();
+        Self::new(backend, &app_name)
+    }
+
+    pub fn with_codec(mut self, codec: CacheCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// 在当前命名空间下再加一级，如`cache.child("user")`的key为`{namespace}:user:{key}`
+    pub fn child(&self, namespace: &str) -> Self {
+        Self {
+            backend: self.backend.clone(),
+            namespace: self.key(namespace),
+            codec: self.codec,
+        }
+    }
+
+    /// 加上命名空间后实际使用的key
+    pub fn key(&self, key: &str) -> String {
+        if self.namespace.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}:{}", self.namespace, key)
+        }
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        match self.backend.get(&self.key(key)).await? {
+            Some(value) => Ok(Some(self.codec.decode(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn set<T: Serialize>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        self.backend
+            .set(&self.key(key), self.codec.encode(value)?, ttl)
+            .await
+    }
+
+    pub async fn set_nx<T: Serialize>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<bool> {
+        self.backend
+            .set_nx(&self.key(key), self.codec.encode(value)?, ttl)
+            .await
+    }
+
+    pub async fn delete(&self, key: &str) -> anyhow::Result<bool> {
+        self.backend.delete(&self.key(key)).await
+    }
+
+    pub async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        self.backend.exists(&self.key(key)).await
+    }
+
+    pub async fn mget<T: DeserializeOwned>(&self, keys: &[&str]) -> anyhow::Result<Vec<Option<T>>> {
+        let keys: Vec<String> = keys.iter().map(|key| self.key(key)).collect();
+        self.backend
+            .mget(&keys)
+            .await?
+            .into_iter()
+            .map(|value| match value {
+                Some(value) => Ok(Some(self.codec.decode(&value)?)),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    pub async fn mset<T: Serialize>(
+        &self,
+        items: &[(&str, T)],
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        let mut encoded = Vec::with_capacity(items.len());
+        for (key, value) in items {
+            encoded.push((self.key(key), self.codec.encode(value)?));
+        }
+        self.backend.mset(encoded, ttl).await
+    }
+
+    pub async fn ttl(&self, key: &str) -> anyhow::Result<KvTtl> {
+        self.backend.ttl(&self.key(key)).await
+    }
+
+    pub async fn expire(&self, key: &str, ttl: Duration) -> anyhow::Result<bool> {
+        self.backend.expire(&self.key(key), ttl).await
+    }
+
+    /// 缓存中没有时调用loader加载并写入缓存，缓存读写出错时直接使用loader的结果
+    pub async fn get_or_set_with<T, F, Fut>(
+        &self,
+        key: &str,
+        ttl: Option<Duration>,
+        loader: F,
+    ) -> anyhow::Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        match self.get(key).await {
+            Ok(Some(value)) => return Ok(value),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("cache get {} error: {:?}", self.key(key), e),
+        }
+        let value = loader().await?;
+        if let Err(e) = self.set(key, &value, ttl).await {
+            tracing::warn!("cache set {} error: {:?}", self.key(key), e);
+        }
+        Ok(value)
+    }
+
+    pub async fn hget<T: DeserializeOwned>(
+        &self,
+        key: &str,
+        field: &str,
+    ) -> anyhow::Result<Option<T>> {
+        match self.backend.hget(&self.key(key), field).await? {
+            Some(value) => Ok(Some(self.codec.decode(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn hset<T: Serialize>(
+        &self,
+        key: &str,
+        field: &str,
+        value: &T,
+    ) -> anyhow::Result<()> {
+        self.backend
+            .hset(&self.key(key), field, self.codec.encode(value)?)
+            .await
+    }
+
+    pub async fn hdel(&self, key: &str, field: &str) -> anyhow::Result<bool> {
+        self.backend.hdel(&self.key(key), field).await
+    }
+
+    pub async fn hgetall<T: DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> anyhow::Result<HashMap<String, T>> {
+        self.backend
+            .hgetall(&self.key(key))
+            .await?
+            .into_iter()
+            .map(|(field, value)| Ok((field, self.codec.decode(&value)?)))
+            .collect()
+    }
+
+    pub async fn rpush<T: Serialize>(&self, key: &str, value: &T) -> anyhow::Result<u64> {
+        self.backend
+            .rpush(&self.key(key), self.codec.encode(value)?)
+            .await
+    }
+
+    pub async fn lpop<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        match self.backend.lpop(&self.key(key)).await? {
+            Some(value) => Ok(Some(self.codec.decode(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn lrange<T: DeserializeOwned>(
+        &self,
+        key: &str,
+        start: i64,
+        stop: i64,
+    ) -> anyhow::Result<Vec<T>> {
+        self.backend
+            .lrange(&self.key(key), start, stop)
+            .await?
+            .iter()
+            .map(|value| self.codec.decode(value))
+            .collect()
+    }
+
+    pub async fn llen(&self, key: &str) -> anyhow::Result<u64> {
+        self.backend.llen(&self.key(key)).await
+    }
+
+    pub async fn sadd<T: Serialize>(&self, key: &str, member: &T) -> anyhow::Result<bool> {
+        self.backend
+            .sadd(&self.key(key), self.codec.encode(member)?)
+            .await
+    }
+
+    pub async fn srem<T: Serialize>(&self, key: &str, member: &T) -> anyhow::Result<bool> {
+        self.backend
+            .srem(&self.key(key), self.codec.encode(member)?)
+            .await
+    }
+
+    pub async fn smembers<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Vec<T>> {
+        self.backend
+            .smembers(&self.key(key))
+            .await?
+            .iter()
+            .map(|value| self.codec.decode(value))
+            .collect()
+    }
+
+    pub async fn sismember<T: Serialize>(&self, key: &str, member: &T) -> anyhow::Result<bool> {
+        self.backend
+            .sismember(&self.key(key), self.codec.encode(member)?)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+
+    #[test]
+    fn codec_test() {
+        let value: HashMap<String, Vec<u32>> =
+            HashMap::from([("a".to_string(), vec![1, 2]), ("b".to_string(), vec![])]);
+        for codec in [CacheCodec::Json, CacheCodec::Bincode] {
+            let encoded = codec.encode(&value).unwrap();
+            let decoded: HashMap<String, Vec<u32>> = codec.decode(&encoded).unwrap();
+            assert_eq!(decoded, value);
+        }
+        assert_eq!(KvTtl::from_pttl(-2), KvTtl::Missing);
+        assert_eq!(KvTtl::from_pttl(-1), KvTtl::Persistent);
+        assert_eq!(
+            KvTtl::from_pttl(1500),
+            KvTtl::Expires(Duration::from_millis(1500))
+        );
+    }
+}
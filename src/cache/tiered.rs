@@ -0,0 +1,291 @@
+use super::Cache;
+use crate::time_util::get_now_millis;
+use dashmap::DashMap;
+use lru::LruCache;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 写入redis的值，记录加载耗时和过期时间用于提前刷新
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    value: Option<T>, // None表示数据不存在，用于缓存空结果
+    delta_ms: u64,    // 加载耗时
+    expires_at: u64,  // 过期时间，毫秒时间戳
+}
+
+struct LocalEntry {
+    value: Vec<u8>, // 按Cache的codec序列化的Option<T>
+    expires_at: Instant,
+}
+
+/// 进程内LRU + redis的两级缓存
+/// - 同一进程内对同一个key的并发加载只执行一次loader
+/// - 按加载耗时在过期前随机提前刷新(XFetch)，避免热点key同时过期击穿数据库
+/// - loader返回None时按negative_ttl缓存空结果
+/// - invalidate通过redis pub/sub通知其他实例清除本地缓存，需启用`redis-fred`并调用start_invalidation_listener
+pub struct TieredCache {
+    pub remote: Cache,
+    pub local_ttl: Duration,    // 本地缓存的最长时间，超过后重新读取redis
+    pub negative_ttl: Duration, // 空结果的缓存时间
+    pub beta: f64,              // 提前刷新的系数，越大越早刷新，0为不提前刷新
+    local: Arc<Mutex<LruCache<String, LocalEntry>>>,
+    inflight: DashMap<String, Arc<tokio::sync::Mutex<()>>>,
+}
+
+impl TieredCache {
+    pub fn new(remote: Cache, capacity: usize) -> Self {
+        Self {
+            remote,
+            local_ttl: Duration::from_secs(30),
+            negative_ttl: Duration::from_secs(60),
+            beta: 1.0,
+            local: Arc::new(Mutex::new(LruCache::new(
+                NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN),
+            ))),
+            inflight: DashMap::default(),
+        }
+    }
+
+    /// 使用redisfred::init_redis_pool初始化的连接池
+    #[cfg(feature = "redis-fred")]
+    pub fn fred(namespace: &str, capacity: usize) -> Self {
+        Self::new(Cache::fred(namespace), capacity)
+    }
+
+    pub fn with_local_ttl(mut self, local_ttl: Duration) -> Self {
+        self.local_ttl = local_ttl;
+        self
+    }
+
+    pub fn with_negative_ttl(mut self, negative_ttl: Duration) -> Self {
+        self.negative_ttl = negative_ttl;
+        self
+    }
+
+    pub fn with_beta(mut self, beta: f64) -> Self {
+        self.beta = beta;
+        self
+    }
+
+    /// 依次读取本地缓存和redis，都没有或需要提前刷新时调用loader加载，
+    /// loader出错且redis中还有未过期的值时返回旧值
+    pub async fn get_or_load<T, F, Fut>(
+        &self,
+        key: &str,
+        ttl: Duration,
+        loader: F,
+    ) -> anyhow::Result<Option<T>>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<Option<T>>>,
+    {
+        let full_key = self.remote.key(key);
+        if let Some(value) = self.get_local(&full_key) {
+            return Ok(value);
+        }
+
+        let cached: Option<Envelope<T>> = match self.remote.get(key).await {
+            Ok(cached) => cached,
+            Err(e) => {
+                tracing::warn!("cache get {} error: {:?}", full_key, e);
+                None
+            }
+        };
+        if let Some(envelope) = &cached {
+            let now = get_now_millis();
+            if !self.should_refresh(envelope, now) {
+                self.set_local(
+                    &full_key,
+                    &envelope.value,
+                    Duration::from_millis(envelope.expires_at.saturating_sub(now)),
+                );
+                return Ok(cached.and_then(|envelope| envelope.value));
+            }
+        }
+
+        let lock = self.inflight.entry(full_key.clone()).or_default().clone();
+        let _guard = match lock.try_lock() {
+            Ok(guard) => guard,
+            // 已有其他请求在刷新，直接返回还未过期的旧值
+            Err(_) if cached.is_some() => return Ok(cached.and_then(|envelope| envelope.value)),
+            Err(_) => lock.lock().await,
+        };
+        // 写入缓存后才移除，panic或future被取消时也会移除
+        let _inflight = InflightGuard {
+            inflight: &self.inflight,
+            full_key: &full_key,
+            lock: &lock,
+        };
+        // 等待期间或读取redis之后其他请求可能已经加载完成
+        if let Some(value) = self.get_local(&full_key) {
+            return Ok(value);
+        }
+
+        let start = Instant::now();
+        let loaded = loader().await;
+        let value = match loaded {
+            Ok(value) => value,
+            Err(e) => {
+                return match cached {
+                    Some(envelope) => {
+                        tracing::warn!("cache load {} error, use stale value: {:?}", full_key, e);
+                        Ok(envelope.value)
+                    }
+                    None => Err(e),
+                };
+            }
+        };
+
+        let ttl = if value.is_some() {
+            ttl
+        } else {
+            self.negative_ttl
+        };
+        let envelope = Envelope {
+            value,
+            delta_ms: start.elapsed().as_millis() as u64,
+            expires_at: get_now_millis() + ttl.as_millis() as u64,
+        };
+        if let Err(e) = self.remote.set(key, &envelope, Some(ttl)).await {
+            tracing::warn!("cache set {} error: {:?}", full_key, e);
+        }
+        self.set_local(&full_key, &envelope.value, ttl);
+        Ok(envelope.value)
+    }
+
+    /// 删除redis和本地缓存，并通知其他实例清除本地缓存
+    pub async fn invalidate(&self, key: &str) -> anyhow::Result<()> {
+        let full_key = self.remote.key(key);
+        self.invalidate_local(&full_key);
+        self.remote.delete(key).await?;
+        #[cfg(feature = "redis-fred")]
+        if let Some(redis) = crate::redisfred::try_get_redis_pool() {
+            use fred::interfaces::PubsubInterface;
+            let _: i64 = redis.pool.next().publish(self.channel(), full_key).await?;
+        }
+        Ok(())
+    }
+
+    /// 只清除本进程的缓存，参数为加上命名空间后的key
+    pub fn invalidate_local(&self, full_key: &str) {
+        self.local.lock().unwrap().pop(full_key);
+    }
+
+    /// 订阅其他实例发出的失效通知，收到后清除本地缓存
+    #[cfg(feature = "redis-fred")]
+    pub async fn start_invalidation_listener(&self) -> anyhow::Result<tokio::task::JoinHandle<()>> {
+        use fred::interfaces::{EventInterface, PubsubInterface};
+        let subscriber = crate::redisfred::new_subscriber_client().await?;
+        subscriber.subscribe(self.channel()).await?;
+        let mut rx = subscriber.message_rx();
+        let local = self.local.clone();
+        Ok(tokio::spawn(async move {
+            let _subscriber = subscriber;
+            loop {
+                match rx.recv().await {
+                    Ok(message) => {
+                        if let Some(key) = message.value.as_string() {
+                            local.lock().unwrap().pop(&key);
+                        }
+                    }
+                    // 丢失了部分通知，无法确定哪些key已失效
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                        local.lock().unwrap().clear();
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }))
+    }
+
+    #[cfg_attr(not(feature = "redis-fred"), allow(dead_code))]
+    fn channel(&self) -> String {
+        self.remote.key("invalidate")
+    }
+
+    fn should_refresh<T>(&self, envelope: &Envelope<T>, now: u64) -> bool {
+        // XFetch: now - delta * beta * ln(rand) >= expires_at
+        let rand = 1.0 - rand::random::<f64>();
+        now as f64 - envelope.delta_ms as f64 * self.beta * rand.ln() >= envelope.expires_at as f64
+    }
+
+    fn get_local<T: DeserializeOwned>(&self, full_key: &str) -> Option<Option<T>> {
+        let mut local = self.local.lock().unwrap();
+        let entry = local.get(full_key)?;
+        if entry.expires_at <= Instant::now() {
+            local.pop(full_key);
+            return None;
+        }
+        self.remote.codec.decode(&entry.value).ok()
+    }
+
+    fn set_local<T: Serialize>(&self, full_key: &str, value: &Option<T>, ttl: Duration) {
+        let ttl = ttl.min(self.local_ttl);
+        if ttl.is_zero() {
+            return;
+        }
+        if let Ok(value) = self.remote.codec.encode(value) {
+            self.local.lock().unwrap().put(
+                full_key.to_string(),
+                LocalEntry {
+                    value,
+                    expires_at: Instant::now() + ttl,
+                },
+            );
+        }
+    }
+}
+
+/// 加载完成后移除inflight中的锁，只移除自己持有的锁，不影响之后新建的锁
+struct InflightGuard<'a> {
+    inflight: &'a DashMap<String, Arc<tokio::sync::Mutex<()>>>,
+    full_key: &'a str,
+    lock: &'a Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Drop for InflightGuard<'_> {
+    fn drop(&mut self) {
+        self.inflight
+            .remove_if(self.full_key, |_, lock| Arc::ptr_eq(lock, self.lock));
+    }
+}
+
+#[cfg(test)]
+mod tiered_tests {
+    use super::*;
+    use crate::kv::MemoryKvStore;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn concurrent_load_test() {
+        let cache = TieredCache::new(Cache::new(Arc::new(MemoryKvStore::new()), "test"), 10);
+        let loads = AtomicUsize::new(0);
+        let results = futures::future::join_all((0..20).map(|_| {
+            cache.get_or_load("a", Duration::from_secs(60), || async {
+                loads.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok(Some(1))
+            })
+        }))
+        .await;
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert!(results.into_iter().all(|res| res.unwrap() == Some(1)));
+        assert!(cache.inflight.is_empty());
+
+        // loader被取消时不会留下inflight中的锁
+        let res = tokio::time::timeout(
+            Duration::from_millis(10),
+            cache.get_or_load("b", Duration::from_secs(60), || async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(Some(2))
+            }),
+        )
+        .await;
+        assert!(res.is_err());
+        assert!(cache.inflight.is_empty());
+    }
+}
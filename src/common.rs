@@ -76,6 +76,7 @@ pub fn get_file_end_md5(file_path: &str, size: u32) -> String {
     md5_str
 }
 
+#[deprecated(note = "use ApiClient, which trims the base url itself")]
 pub fn strip_url_last_slash(url: &String) -> String {
     let url = if url.ends_with("/") {
         url.strip_suffix("/").unwrap().to_string()
@@ -0,0 +1,1032 @@
+use crate::db::{try_get_database, with_actor, SoftDelete, PRIMARY};
+use crate::permission::{
+    check_and_verify, register_permission_route, try_get_pmap, PermissionRoute,
+};
+use crate::response::{NoneBodyData, Page, PageQuery, Response};
+use actix_web::{web, HttpRequest, HttpResponse, Scope};
+use sea_orm::sea_query::{ColumnType, SimpleExpr};
+use sea_orm::{
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait,
+    IdenStatic, IntoActiveModel, Iterable, Order, PaginatorTrait, PrimaryKeyToColumn,
+    PrimaryKeyTrait, QueryFilter, QueryOrder, QuerySelect, Select, Value,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use utoipa::openapi::path::{
+    HttpMethod, OperationBuilder, Parameter, ParameterBuilder, ParameterIn,
+};
+use utoipa::openapi::request_body::RequestBodyBuilder;
+use utoipa::openapi::schema::{Array, Object, ObjectBuilder, Schema, Type};
+use utoipa::openapi::{ComponentsBuilder, Content, OpenApi, Ref, RefOr, Required, ResponseBuilder};
+use utoipa::{PartialSchema, ToSchema};
+
+/// CrudResource生成的接口
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrudAction {
+    List,   // GET {path}
+    Get,    // GET {path}/{id}
+    Create, // POST {path}
+    Update, // PUT {path}/{id}，只更新请求中给出的字段
+    Delete, // DELETE {path}/{id}
+}
+
+impl CrudAction {
+    pub const ALL: [CrudAction; 5] = [
+        CrudAction::List,
+        CrudAction::Get,
+        CrudAction::Create,
+        CrudAction::Update,
+        CrudAction::Delete,
+    ];
+
+    /// 默认的RBAC action名字
+    pub fn name(&self) -> &'static str {
+        match self {
+            CrudAction::List => "list",
+            CrudAction::Get => "get",
+            CrudAction::Create => "create",
+            CrudAction::Update => "update",
+            CrudAction::Delete => "delete",
+        }
+    }
+
+    fn method(&self) -> &'static str {
+        match self {
+            CrudAction::List | CrudAction::Get => "GET",
+            CrudAction::Create => "POST",
+            CrudAction::Update => "PUT",
+            CrudAction::Delete => "DELETE",
+        }
+    }
+
+    fn with_id(&self) -> bool {
+        matches!(
+            self,
+            CrudAction::Get | CrudAction::Update | CrudAction::Delete
+        )
+    }
+}
+
+/// 软删除的deleted_at字段和取当前时间的函数
+type DeletedAt<C> = (C, fn() -> Value);
+
+/// 列表查询中的保留参数，其余参数都作为过滤条件
+const PAGE_PARAMS: [&str; 3] = ["page", "page_size", "sort"];
+
+/// 由Audited填充的字段，创建和更新接口始终忽略请求中的值
+const AUDIT_COLUMNS: [&str; 5] = [
+    "created_at",
+    "created_by",
+    "updated_at",
+    "updated_by",
+    "deleted_at",
+];
+
+/// 为sea-orm实体生成增删改查接口的scope，每个接口调用check_and_verify检查权限，返回Response，
+/// 接口对应的权限(page, action)由register注册到PermissionMap.pmap，需在init_pmap之前调用，
+/// scope不会注册权限，可以在HttpServer::new的闭包中为每个worker调用
+/// ```ignore
+/// let users = CrudResource::<user::Entity>::new("/api/v1/user", "user")
+///     .with_soft_delete()
+///     .with_sort_columns(vec![user::Column::Id, user::Column::CreatedAt]);
+/// users.register();
+/// init_pmap(...);
+/// HttpServer::new(move || App::new().service(users.scope()))
+/// ```
+/// 列表接口的查询参数
+/// - 分页: `page`、`page_size`
+/// - 排序: `sort=-created_at,name`，`-`为倒序，默认按主键正序
+/// - 过滤: `name=abc`、`age__gte=18`、`status__in=1,2`、`deleted_at__null=true`，
+///   支持eq、ne、gt、gte、lt、lte、like、in、null
+pub struct CrudResource<E: EntityTrait> {
+    path: &'static str,
+    page: &'static str,
+    actions: Vec<(CrudAction, &'static str)>,
+    database: String,
+    filter_columns: Option<Vec<E::Column>>,
+    sort_columns: Option<Vec<E::Column>>,
+    writable_columns: Option<Vec<E::Column>>,
+    soft_delete: Option<DeletedAt<E::Column>>,
+    _entity: PhantomData<E>,
+}
+
+impl<E: EntityTrait> Clone for CrudResource<E> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path,
+            page: self.page,
+            actions: self.actions.clone(),
+            database: self.database.clone(),
+            filter_columns: self.filter_columns.clone(),
+            sort_columns: self.sort_columns.clone(),
+            writable_columns: self.writable_columns.clone(),
+            soft_delete: self.soft_delete,
+            _entity: PhantomData,
+        }
+    }
+}
+
+impl<E: EntityTrait> CrudResource<E> {
+    /// path为scope的完整路径，page为RBAC的page名字，默认生成全部接口，使用db::PRIMARY数据库
+    pub fn new(path: &'static str, page: &'static str) -> Self {
+        Self {
+            path: path.trim_end_matches('/'),
+            page,
+            actions: CrudAction::ALL
+                .iter()
+                .map(|action| (*action, action.name()))
+                .collect(),
+            database: PRIMARY.to_string(),
+            filter_columns: None,
+            sort_columns: None,
+            writable_columns: None,
+            soft_delete: None,
+            _entity: PhantomData,
+        }
+    }
+
+    /// 只生成给出的接口
+    pub fn with_actions(mut self, actions: &[CrudAction]) -> Self {
+        self.actions.retain(|(action, _)| actions.contains(action));
+        self
+    }
+
+    /// 修改接口对应的RBAC action名字
+    pub fn with_action_name(mut self, action: CrudAction, name: &'static str) -> Self {
+        for each in self.actions.iter_mut() {
+            if each.0 == action {
+                each.1 = name;
+            }
+        }
+        self
+    }
+
+    /// 使用db::init_database注册的其他数据库
+    pub fn with_database(mut self, name: &str) -> Self {
+        self.database = name.to_string();
+        self
+    }
+
+    /// 允许过滤的字段，默认为全部字段
+    pub fn with_filter_columns(mut self, columns: Vec<E::Column>) -> Self {
+        self.filter_columns = Some(columns);
+        self
+    }
+
+    /// 允许排序的字段，默认为全部字段
+    pub fn with_sort_columns(mut self, columns: Vec<E::Column>) -> Self {
+        self.sort_columns = Some(columns);
+        self
+    }
+
+    /// 创建和更新接口允许写入的字段，默认为主键以外的全部字段，请求中的其他字段被忽略，
+    /// 审计字段和软删除字段始终不可写，非自增主键需要在这里给出才能在创建时指定
+    pub fn with_writable_columns(mut self, columns: Vec<E::Column>) -> Self {
+        self.writable_columns = Some(columns);
+        self
+    }
+
+    /// 查询时排除已删除的记录，删除接口改为设置deleted_at
+    pub fn with_soft_delete(mut self) -> Self
+    where
+        E: SoftDelete,
+    {
+        self.soft_delete = Some((E::deleted_at_column(), E::deleted_at_value));
+        self
+    }
+
+    /// 接口的路由，格式与PermissionMap.pmap一致，均为首尾锚定的正则表达式，
+    /// 避免list的权限匹配到`{path}/{id}`或以path开头的其他路径
+    pub fn routes(&self) -> Vec<(String, &'static str, &'static str)> {
+        self.actions
+            .iter()
+            .map(|(action, name)| {
+                let path = regex::escape(self.path);
+                let route = if action.with_id() {
+                    format!("^{} {}/[^/]+$", action.method(), path)
+                } else {
+                    format!("^{} {}$", action.method(), path)
+                };
+                (route, self.page, *name)
+            })
+            .collect()
+    }
+
+    /// 将接口的权限注册到PermissionMap.pmap，需在init_pmap之前调用，之后调用只检查路由是否已在pmap中
+    pub fn register(&self) {
+        let pmap = try_get_pmap();
+        for (route, page, action) in self.routes() {
+            if let Some(pmap) = pmap {
+                if !pmap.pmap.contains_key(route.as_str()) {
+                    tracing::warn!(
+                        "permission route registered after init_pmap is ignored: {}",
+                        route
+                    );
+                }
+                continue;
+            }
+            register_permission_route(PermissionRoute {
+                route: Box::leak(route.into_boxed_str()),
+                page,
+                action,
+                whitelist: false,
+            });
+        }
+    }
+
+    fn database(&self) -> anyhow::Result<DatabaseConnection> {
+        try_get_database(&self.database)
+            .ok_or_else(|| anyhow::anyhow!("数据库{}未初始化", self.database))
+    }
+
+    fn has_action(&self, action: CrudAction) -> bool {
+        self.actions.iter().any(|(each, _)| *each == action)
+    }
+
+    fn find(&self) -> Select<E> {
+        match self.soft_delete {
+            Some((column, _)) => E::find().filter(column.is_null()),
+            None => E::find(),
+        }
+    }
+
+    fn find_by_id<T>(&self, id: T) -> Select<E>
+    where
+        T: Into<<E::PrimaryKey as PrimaryKeyTrait>::ValueType>,
+    {
+        match self.soft_delete {
+            Some((column, _)) => E::find_by_id(id).filter(column.is_null()),
+            None => E::find_by_id(id),
+        }
+    }
+
+    fn is_writable(&self, column: &E::Column) -> bool {
+        let name = column.as_str();
+        if AUDIT_COLUMNS.contains(&name) {
+            return false;
+        }
+        if let Some((deleted_at, _)) = self.soft_delete {
+            if deleted_at.as_str() == name {
+                return false;
+            }
+        }
+        match &self.writable_columns {
+            Some(writable) => writable.iter().any(|each| each.as_str() == name),
+            None => !is_primary_key::<E>(column),
+        }
+    }
+
+    fn is_auto_increment(column: &E::Column) -> bool {
+        is_primary_key::<E>(column) && E::PrimaryKey::auto_increment()
+    }
+
+    /// 将创建请求转为Model，可写且非空的字段需要在请求中给出，不可写的字段使用占位值
+    fn create_model(&self, json: serde_json::Value) -> anyhow::Result<E::Model>
+    where
+        E::Model: DeserializeOwned,
+    {
+        let serde_json::Value::Object(mut object) = json else {
+            return Err(anyhow::anyhow!("请求数据需为JSON对象"));
+        };
+        for column in E::Column::iter() {
+            if Self::is_auto_increment(&column) || !self.is_writable(&column) {
+                let def = column.def();
+                object
+                    .entry(column.as_str())
+                    .or_insert_with(|| placeholder(def.get_column_type(), def.is_null()));
+            }
+        }
+        Ok(serde_json::from_value(serde_json::Value::Object(object))?)
+    }
+
+    fn column(&self, name: &str, allowed: &Option<Vec<E::Column>>) -> Option<E::Column> {
+        let column = E::Column::iter().find(|column| column.as_str() == name)?;
+        match allowed {
+            Some(allowed) if !allowed.iter().any(|each| each.as_str() == name) => None,
+            _ => Some(column),
+        }
+    }
+
+    /// 按查询参数添加过滤和排序条件
+    fn list_query(&self, params: &HashMap<String, String>) -> anyhow::Result<Select<E>> {
+        let mut select = self.find();
+        for (key, value) in params.iter() {
+            if PAGE_PARAMS.contains(&key.as_str()) {
+                continue;
+            }
+            let (name, op) = key.split_once("__").unwrap_or((key.as_str(), "eq"));
+            let column = self
+                .column(name, &self.filter_columns)
+                .ok_or_else(|| anyhow::anyhow!("不支持的过滤字段-{}", name))?;
+            select = select.filter(filter_expr(column, op, value)?);
+        }
+        let mut sorted = false;
+        if let Some(sort) = params.get("sort") {
+            for each in sort.split(',').filter(|each| !each.is_empty()) {
+                let (name, order) = match each.strip_prefix('-') {
+                    Some(name) => (name, Order::Desc),
+                    None => (each.trim_start_matches('+'), Order::Asc),
+                };
+                let column = self
+                    .column(name, &self.sort_columns)
+                    .ok_or_else(|| anyhow::anyhow!("不支持的排序字段-{}", name))?;
+                select = select.order_by(column, order);
+                sorted = true;
+            }
+        }
+        if !sorted {
+            for key in E::PrimaryKey::iter() {
+                select = select.order_by(key.into_column(), Order::Asc);
+            }
+        }
+        Ok(select)
+    }
+}
+
+/// 过滤条件，查询参数的值按字段类型转换
+fn filter_expr<C: ColumnTrait>(column: C, op: &str, value: &str) -> anyhow::Result<SimpleExpr> {
+    let column_type = column.def().get_column_type().clone();
+    let parse = |value: &str| parse_value(&column_type, value);
+    Ok(match op {
+        "eq" => column.eq(parse(value)?),
+        "ne" => column.ne(parse(value)?),
+        "gt" => column.gt(parse(value)?),
+        "gte" => column.gte(parse(value)?),
+        "lt" => column.lt(parse(value)?),
+        "lte" => column.lte(parse(value)?),
+        "like" => column.like(value),
+        "in" => column.is_in(
+            value
+                .split(',')
+                .map(parse)
+                .collect::<anyhow::Result<Vec<Value>>>()?,
+        ),
+        "null" => match value {
+            "true" | "1" => column.is_null(),
+            _ => column.is_not_null(),
+        },
+        _ => return Err(anyhow::anyhow!("不支持的过滤条件-{}", op)),
+    })
+}
+
+fn parse_value(column_type: &ColumnType, value: &str) -> anyhow::Result<Value> {
+    let invalid = |_| anyhow::anyhow!("参数值{}与字段类型{:?}不符", value, column_type);
+    Ok(match column_type {
+        ColumnType::TinyInteger
+        | ColumnType::SmallInteger
+        | ColumnType::Integer
+        | ColumnType::BigInteger => {
+            Value::from(value.parse::<i64>().map_err(|e| invalid(e.to_string()))?)
+        }
+        ColumnType::TinyUnsigned
+        | ColumnType::SmallUnsigned
+        | ColumnType::Unsigned
+        | ColumnType::BigUnsigned => {
+            Value::from(value.parse::<u64>().map_err(|e| invalid(e.to_string()))?)
+        }
+        ColumnType::Float | ColumnType::Double => {
+            Value::from(value.parse::<f64>().map_err(|e| invalid(e.to_string()))?)
+        }
+        ColumnType::Boolean => Value::from(matches!(value, "true" | "1")),
+        _ => Value::from(value),
+    })
+}
+
+/// 创建时请求中没有给出的不可写字段(自增主键、审计字段等)使用的占位值，使JSON能反序列化为Model，
+/// 这些字段随后设置为NotSet，由数据库或Audited填充
+fn placeholder(column_type: &ColumnType, nullable: bool) -> serde_json::Value {
+    if nullable {
+        return serde_json::Value::Null;
+    }
+    match column_type {
+        ColumnType::Char(_) | ColumnType::String(_) | ColumnType::Text => "".into(),
+        ColumnType::TinyInteger
+        | ColumnType::SmallInteger
+        | ColumnType::Integer
+        | ColumnType::BigInteger
+        | ColumnType::TinyUnsigned
+        | ColumnType::SmallUnsigned
+        | ColumnType::Unsigned
+        | ColumnType::BigUnsigned
+        | ColumnType::Year
+        | ColumnType::Float
+        | ColumnType::Double
+        | ColumnType::Decimal(_)
+        | ColumnType::Money(_) => 0.into(),
+        ColumnType::Boolean => false.into(),
+        ColumnType::DateTime | ColumnType::Timestamp => "1970-01-01T00:00:00".into(),
+        ColumnType::TimestampWithTimeZone => "1970-01-01T00:00:00Z".into(),
+        ColumnType::Date => "1970-01-01".into(),
+        ColumnType::Time => "00:00:00".into(),
+        ColumnType::Uuid => uuid::Uuid::nil().to_string().into(),
+        ColumnType::Blob | ColumnType::Binary(_) | ColumnType::VarBinary(_) => {
+            serde_json::json!([])
+        }
+        _ => serde_json::Value::Null,
+    }
+}
+
+/// 将更新请求中的字段覆盖到已有记录上，按Model的serde字段名合并，未给出的字段保持原值
+fn merge_model<E>(existing: &E::Model, json: serde_json::Value) -> anyhow::Result<E::Model>
+where
+    E: EntityTrait,
+    E::Model: Serialize + DeserializeOwned,
+{
+    let serde_json::Value::Object(changes) = json else {
+        return Err(anyhow::anyhow!("请求数据需为JSON对象"));
+    };
+    let mut merged = serde_json::to_value(existing)?;
+    if let Some(object) = merged.as_object_mut() {
+        for (key, value) in changes.into_iter() {
+            if object.contains_key(&key) {
+                object.insert(key, value);
+            }
+        }
+    }
+    Ok(serde_json::from_value(merged)?)
+}
+
+fn is_null(value: &Option<Value>) -> bool {
+    value.as_ref().is_none_or(|value| *value == value.as_null())
+}
+
+fn is_primary_key<E: EntityTrait>(column: &E::Column) -> bool {
+    E::PrimaryKey::iter().any(|key| key.into_column().as_str() == column.as_str())
+}
+
+fn error_response(e: anyhow::Error) -> HttpResponse {
+    Response::<NoneBodyData>::from(e).finished()
+}
+
+fn bad_request(e: anyhow::Error) -> HttpResponse {
+    Response::<NoneBodyData>::bad_request(&format!("{}", e)).finished()
+}
+
+impl<E> CrudResource<E>
+where
+    E: EntityTrait,
+    E::Model: Serialize + DeserializeOwned + Debug + Send + Sync + IntoActiveModel<E::ActiveModel>,
+    E::ActiveModel: ActiveModelBehavior + Send,
+    <E::PrimaryKey as PrimaryKeyTrait>::ValueType: DeserializeOwned,
+{
+    /// 生成actix的scope，接口权限需要另外调用register注册
+    pub fn scope(&self) -> Scope {
+        let resource = Arc::new(self.clone());
+        let mut scope = web::scope(self.path);
+        if self.has_action(CrudAction::List) {
+            let resource = resource.clone();
+            scope = scope.route(
+                "",
+                web::get().to(
+                    move |req: HttpRequest, query: web::Query<HashMap<String, String>>| {
+                        let resource = resource.clone();
+                        async move { resource.list(req, query.into_inner()).await }
+                    },
+                ),
+            );
+        }
+        if self.has_action(CrudAction::Create) {
+            let resource = resource.clone();
+            scope = scope.route(
+                "",
+                web::post().to(
+                    move |req: HttpRequest, body: web::Json<serde_json::Value>| {
+                        let resource = resource.clone();
+                        async move { resource.create(req, body.into_inner()).await }
+                    },
+                ),
+            );
+        }
+        if self.has_action(CrudAction::Get) {
+            let resource = resource.clone();
+            scope = scope.route(
+                "/{id}",
+                web::get().to(move |req: HttpRequest, id: web::Path<<E::PrimaryKey as PrimaryKeyTrait>::ValueType>| {
+                    let resource = resource.clone();
+                    async move { resource.get(req, id.into_inner()).await }
+                }),
+            );
+        }
+        if self.has_action(CrudAction::Update) {
+            let resource = resource.clone();
+            scope = scope.route(
+                "/{id}",
+                web::put().to(
+                    move |req: HttpRequest,
+                          id: web::Path<<E::PrimaryKey as PrimaryKeyTrait>::ValueType>,
+                          body: web::Json<serde_json::Value>| {
+                        let resource = resource.clone();
+                        async move {
+                            resource
+                                .update(req, id.into_inner(), body.into_inner())
+                                .await
+                        }
+                    },
+                ),
+            );
+        }
+        if self.has_action(CrudAction::Delete) {
+            let resource = resource.clone();
+            scope = scope.route(
+                "/{id}",
+                web::delete().to(move |req: HttpRequest, id: web::Path<<E::PrimaryKey as PrimaryKeyTrait>::ValueType>| {
+                    let resource = resource.clone();
+                    async move { resource.delete(req, id.into_inner()).await }
+                }),
+            );
+        }
+        scope
+    }
+
+    async fn list(
+        &self,
+        req: HttpRequest,
+        params: HashMap<String, String>,
+    ) -> Result<HttpResponse, actix_web::Error> {
+        check_and_verify::<NoneBodyData>(&req).await?;
+        let select = match self.list_query(&params) {
+            Ok(select) => select,
+            Err(e) => return Ok(bad_request(e)),
+        };
+        let query = PageQuery {
+            page: params.get("page").and_then(|page| page.parse().ok()),
+            page_size: params.get("page_size").and_then(|size| size.parse().ok()),
+            cursor: None,
+        };
+        let result = async {
+            let db = self.database()?;
+            let total = select.clone().count(&db).await?;
+            // 不使用Paginator::fetch_page，page过大时其中的乘法会溢出
+            let items = select
+                .limit(query.page_size())
+                .offset(query.offset())
+                .all(&db)
+                .await?;
+            anyhow::Ok(Page::new(items, total, &query))
+        }
+        .await;
+        Ok(match result {
+            Ok(page) => Response::success(page).finished(),
+            Err(e) => error_response(e),
+        })
+    }
+
+    async fn get(
+        &self,
+        req: HttpRequest,
+        id: <E::PrimaryKey as PrimaryKeyTrait>::ValueType,
+    ) -> Result<HttpResponse, actix_web::Error> {
+        check_and_verify::<NoneBodyData>(&req).await?;
+        let result = async {
+            let db = self.database()?;
+            anyhow::Ok(self.find_by_id(id).one(&db).await?)
+        }
+        .await;
+        Ok(match result {
+            Ok(Some(model)) => Response::success(model).finished(),
+            Ok(None) => Response::<NoneBodyData>::nofound("记录不存在").finished(),
+            Err(e) => error_response(e),
+        })
+    }
+
+    async fn create(
+        &self,
+        req: HttpRequest,
+        body: serde_json::Value,
+    ) -> Result<HttpResponse, actix_web::Error> {
+        let access = check_and_verify::<NoneBodyData>(&req).await?;
+        let given = match &body {
+            serde_json::Value::Object(object) => object.keys().cloned().collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+        let model = match self.create_model(body) {
+            Ok(model) => model,
+            Err(e) => return Ok(bad_request(e)),
+        };
+        let mut active_model = model.into_active_model();
+        for column in E::Column::iter() {
+            // 请求中省略的可空字段不写入，使用数据库的默认值
+            let omitted = !given.iter().any(|key| key == column.as_str())
+                && is_null(&active_model.get(column).into_value());
+            if Self::is_auto_increment(&column) || !self.is_writable(&column) || omitted {
+                active_model.not_set(column);
+            }
+        }
+        let result = with_actor(access, async {
+            let db = self.database()?;
+            anyhow::Ok(active_model.insert(&db).await?)
+        })
+        .await;
+        Ok(match result {
+            Ok(model) => Response::success(model).finished(),
+            Err(e) => error_response(e),
+        })
+    }
+
+    async fn update(
+        &self,
+        req: HttpRequest,
+        id: <E::PrimaryKey as PrimaryKeyTrait>::ValueType,
+        body: serde_json::Value,
+    ) -> Result<HttpResponse, actix_web::Error> {
+        let access = check_and_verify::<NoneBodyData>(&req).await?;
+        let result = with_actor(access, async {
+            let db = self.database()?;
+            let Some(existing) = self.find_by_id(id).one(&db).await? else {
+                return Ok(None);
+            };
+            let changes = match merge_model::<E>(&existing, body) {
+                Ok(model) => model.into_active_model(),
+                Err(e) => return Ok(Some(Err(e))),
+            };
+            let mut active_model = existing.into_active_model();
+            for column in E::Column::iter() {
+                if is_primary_key::<E>(&column) || !self.is_writable(&column) {
+                    continue;
+                }
+                let value = changes.get(column).into_value();
+                if value != active_model.get(column).into_value() {
+                    if let Some(value) = value {
+                        active_model.set(column, value);
+                    }
+                }
+            }
+            anyhow::Ok(Some(Ok(active_model.update(&db).await?)))
+        })
+        .await;
+        Ok(match result {
+            Ok(Some(Ok(model))) => Response::success(model).finished(),
+            Ok(Some(Err(e))) => bad_request(e),
+            Ok(None) => Response::<NoneBodyData>::nofound("记录不存在").finished(),
+            Err(e) => error_response(e),
+        })
+    }
+
+    async fn delete(
+        &self,
+        req: HttpRequest,
+        id: <E::PrimaryKey as PrimaryKeyTrait>::ValueType,
+    ) -> Result<HttpResponse, actix_web::Error> {
+        let access = check_and_verify::<NoneBodyData>(&req).await?;
+        let result = with_actor(access, async {
+            let db = self.database()?;
+            let Some(existing) = self.find_by_id(id).one(&db).await? else {
+                return Ok(false);
+            };
+            let mut active_model = existing.into_active_model();
+            match self.soft_delete {
+                Some((column, deleted_at)) => {
+                    active_model.set(column, deleted_at());
+                    active_model.update(&db).await?;
+                }
+                None => {
+                    active_model.delete(&db).await?;
+                }
+            }
+            anyhow::Ok(true)
+        })
+        .await;
+        Ok(match result {
+            Ok(true) => Response::success(NoneBodyData {}).finished(),
+            Ok(false) => Response::<NoneBodyData>::nofound("记录不存在").finished(),
+            Err(e) => error_response(e),
+        })
+    }
+}
+
+impl<E> CrudResource<E>
+where
+    E: EntityTrait,
+    E::Model: ToSchema,
+{
+    /// 将接口和Model的schema加入OpenAPI文档，schema以表名命名，
+    /// 需在apply_security之前调用以便标记鉴权和权限
+    pub fn openapi(&self, openapi: &mut OpenApi) {
+        let name = E::default().table_name().to_string();
+        let mut schemas = Vec::new();
+        <E::Model as ToSchema>::schemas(&mut schemas);
+        let components = openapi
+            .components
+            .get_or_insert_with(|| ComponentsBuilder::new().build());
+        components
+            .schemas
+            .insert(name.clone(), <E::Model as PartialSchema>::schema());
+        components.schemas.extend(schemas);
+
+        let model = Ref::from_schema_name(&name);
+        let page = ObjectBuilder::new()
+            .property("items", Array::new(model.clone()))
+            .property("total", Object::with_type(Type::Integer))
+            .property("page", Object::with_type(Type::Integer))
+            .property("page_size", Object::with_type(Type::Integer))
+            .required("items")
+            .build();
+        let id = ParameterBuilder::new()
+            .name("id")
+            .parameter_in(ParameterIn::Path)
+            .required(Required::True)
+            .schema(Some(Object::with_type(Type::String)))
+            .build();
+        let body = RequestBodyBuilder::new()
+            .content("application/json", Content::new(Some(model.clone())))
+            .required(Some(Required::True))
+            .build();
+
+        for (action, permission) in self.actions.iter() {
+            let (path, method) = if action.with_id() {
+                (format!("{}/{{id}}", self.path), action.method())
+            } else {
+                (self.path.to_string(), action.method())
+            };
+            let mut operation = OperationBuilder::new()
+                .tag(self.page)
+                .operation_id(Some(format!("{}_{}", name, permission)))
+                .summary(Some(format!("{} {}", self.page, permission)));
+            if action.with_id() {
+                operation = operation.parameter(id.clone());
+            }
+            let data: RefOr<Schema> = match action {
+                CrudAction::List => {
+                    for param in ["page", "page_size", "sort"] {
+                        operation = operation.parameter(query_parameter(param));
+                    }
+                    for column in E::Column::iter() {
+                        let allowed = match &self.filter_columns {
+                            Some(columns) => columns.iter().any(|c| c.as_str() == column.as_str()),
+                            None => true,
+                        };
+                        if allowed {
+                            operation = operation.parameter(query_parameter(column.as_str()));
+                        }
+                    }
+                    page.clone().into()
+                }
+                CrudAction::Get => model.clone().into(),
+                CrudAction::Create | CrudAction::Update => {
+                    operation = operation.request_body(Some(body.clone()));
+                    model.clone().into()
+                }
+                CrudAction::Delete => Object::with_type(Type::Object).into(),
+            };
+            let envelope = ObjectBuilder::new()
+                .property("status", Object::with_type(Type::Boolean))
+                .property("code", Object::with_type(Type::Integer))
+                .property("message", data);
+            let operation = operation.response(
+                "200",
+                ResponseBuilder::new()
+                    .description("成功")
+                    .content("application/json", Content::new(Some(envelope))),
+            );
+            let method = match method {
+                "GET" => HttpMethod::Get,
+                "POST" => HttpMethod::Post,
+                "PUT" => HttpMethod::Put,
+                _ => HttpMethod::Delete,
+            };
+            openapi
+                .paths
+                .add_path_operation(path, vec![method], operation.build());
+        }
+    }
+}
+
+fn query_parameter(name: &str) -> Parameter {
+    ParameterBuilder::new()
+        .name(name)
+        .parameter_in(ParameterIn::Query)
+        .required(Required::False)
+        .schema(Some(Object::with_type(Type::String)))
+        .build()
+}
+
+#[cfg(test)]
+mod crud_tests {
+    use super::*;
+    use sea_orm::{DbBackend, QueryTrait};
+
+    mod user {
+        use sea_orm::entity::prelude::*;
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+        #[sea_orm(table_name = "user")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub name: String,
+            pub age: i32,
+            pub deleted_at: Option<DateTime>,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    #[test]
+    fn list_query_test() {
+        let resource = CrudResource::<user::Entity>::new("/api/v1/user/", "user")
+            .with_actions(&[CrudAction::List, CrudAction::Delete])
+            .with_action_name(CrudAction::Delete, "remove")
+            .with_sort_columns(vec![user::Column::Age]);
+        assert_eq!(
+            resource.routes(),
+            vec![
+                ("^GET /api/v1/user$".to_string(), "user", "list"),
+                ("^DELETE /api/v1/user/[^/]+$".to_string(), "user", "remove"),
+            ]
+        );
+        let list = regex::Regex::new(&resource.routes()[0].0).unwrap();
+        assert!(list.is_match("GET /api/v1/user"));
+        assert!(!list.is_match("GET /api/v1/user/1"));
+        assert!(!list.is_match("GET /api/v1/users_admin"));
+
+        let params = HashMap::from([
+            ("age__gte".to_string(), "18".to_string()),
+            ("sort".to_string(), "-age".to_string()),
+            ("page".to_string(), "2".to_string()),
+        ]);
+        let sql = resource
+            .list_query(&params)
+            .unwrap()
+            .build(DbBackend::MySql)
+            .to_string();
+        assert!(sql.contains("`user`.`age` >= 18"));
+        assert!(sql.ends_with("ORDER BY `user`.`age` DESC"));
+
+        let params = HashMap::from([("sort".to_string(), "name".to_string())]);
+        assert!(resource.list_query(&params).is_err());
+        let params = HashMap::from([("age".to_string(), "abc".to_string())]);
+        assert!(resource.list_query(&params).is_err());
+
+        assert!(!resource.is_writable(&user::Column::Id));
+        assert!(!resource.is_writable(&user::Column::DeletedAt));
+        assert!(resource.is_writable(&user::Column::Name));
+        let resource = resource.with_writable_columns(vec![user::Column::Id, user::Column::Age]);
+        assert!(resource.is_writable(&user::Column::Id));
+        assert!(!resource.is_writable(&user::Column::Name));
+
+        let resource = CrudResource::<user::Entity>::new("/api/v1/user", "user");
+        assert!(resource
+            .create_model(serde_json::json!({ "name": "张三" }))
+            .is_err());
+        let model = resource
+            .create_model(serde_json::json!({ "name": "张三", "age": 18 }))
+            .unwrap();
+        assert_eq!((model.id, model.deleted_at), (0, None));
+        let merged =
+            merge_model::<user::Entity>(&model, serde_json::json!({ "age": 20, "other": 1 }))
+                .unwrap();
+        assert_eq!((merged.name.as_str(), merged.age), ("张三", 20));
+    }
+
+    #[cfg(feature = "sqlite")]
+    mod article {
+        use crate::db::Audited;
+        use sea_orm::entity::prelude::*;
+        use serde::{Deserialize, Serialize};
+
+        #[derive(
+            Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize,
+        )]
+        #[sea_orm(rs_type = "String", db_type = "String(StringLen::N(16))")]
+        pub enum Status {
+            #[sea_orm(string_value = "draft")]
+            Draft,
+            #[sea_orm(string_value = "published")]
+            Published,
+        }
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, Audited)]
+        #[sea_orm(table_name = "article")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub title: String,
+            pub status: Status,
+            pub created_by: Option<String>,
+            pub updated_at: DateTime,
+            pub deleted_at: Option<DateTime>,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn sqlite_handler_test() {
+        use crate::db::{init_database, DbConfig};
+        use crate::permission::init_pmap;
+        use crate::token::AccessToken;
+        use actix_web::{http::header, test, App};
+        use sea_orm::ConnectionTrait;
+
+        let db = init_database("crud_test", DbConfig::new("sqlite::memory:"))
+            .await
+            .unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE article (id INTEGER PRIMARY KEY AUTOINCREMENT, title TEXT NOT NULL, \
+             status TEXT NOT NULL, created_by TEXT, updated_at TIMESTAMP NOT NULL, \
+             deleted_at TIMESTAMP)",
+        )
+        .await
+        .unwrap();
+        init_pmap(
+            vec!["^(GET|POST|PUT|DELETE) /api/v1/article"],
+            HashMap::new(),
+            "secret".to_string(),
+            "app".to_string(),
+            true,
+            false,
+        );
+        let token = AccessToken::encode_token(
+            1,
+            &"zhangsan".to_string(),
+            &"张三".to_string(),
+            &"app".to_string(),
+            1,
+            "secret",
+        )
+        .unwrap();
+        let resource = CrudResource::<article::Entity>::new("/api/v1/article", "article")
+            .with_database("crud_test")
+            .with_soft_delete();
+        let app = test::init_service(App::new().service(resource.scope())).await;
+        let call = |req: test::TestRequest| {
+            req.insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+                .to_request()
+        };
+
+        // 缺少非空字段时返回400，审计字段和主键忽略请求中的值
+        let rsp: serde_json::Value = test::call_and_read_body_json(
+            &app,
+            call(
+                test::TestRequest::post()
+                    .uri("/api/v1/article")
+                    .set_json(serde_json::json!({ "title": "标题" })),
+            ),
+        )
+        .await;
+        assert_eq!(rsp["code"], 400);
+        let rsp: serde_json::Value = test::call_and_read_body_json(
+            &app,
+            call(test::TestRequest::post().uri("/api/v1/article").set_json(
+                serde_json::json!({ "id": 100, "title": "标题", "status": "Draft", "created_by": "hacker" }),
+            )),
+        )
+        .await;
+        assert_eq!(rsp["code"], 200, "{}", rsp);
+        let created: article::Model = serde_json::from_value(rsp["message"].clone()).unwrap();
+        assert_eq!(created.id, 1);
+        assert_eq!(created.created_by.as_deref(), Some("zhangsan"));
+
+        // 部分更新不影响其他字段
+        let rsp: serde_json::Value = test::call_and_read_body_json(
+            &app,
+            call(
+                test::TestRequest::put()
+                    .uri("/api/v1/article/1")
+                    .set_json(serde_json::json!({ "title": "新标题" })),
+            ),
+        )
+        .await;
+        assert_eq!(rsp["code"], 200, "{}", rsp);
+        let updated: article::Model = serde_json::from_value(rsp["message"].clone()).unwrap();
+        assert_eq!(updated.title, "新标题");
+        assert_eq!(updated.status, article::Status::Draft);
+
+        let rsp: serde_json::Value = test::call_and_read_body_json(
+            &app,
+            call(test::TestRequest::delete().uri("/api/v1/article/1")),
+        )
+        .await;
+        assert_eq!(rsp["code"], 200, "{}", rsp);
+        let deleted = article::Entity::find_by_id(1)
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(deleted.deleted_at.is_some());
+        for req in [
+            test::TestRequest::get().uri("/api/v1/article/1"),
+            test::TestRequest::put()
+                .uri("/api/v1/article/1")
+                .set_json(serde_json::json!({ "title": "标题" })),
+        ] {
+            let rsp: serde_json::Value = test::call_and_read_body_json(&app, call(req)).await;
+            assert_eq!(rsp["code"], 404);
+        }
+        let rsp: serde_json::Value = test::call_and_read_body_json(
+            &app,
+            call(test::TestRequest::get().uri("/api/v1/article")),
+        )
+        .await;
+        assert_eq!(rsp["message"]["total"], 0);
+    }
+}
@@ -0,0 +1,318 @@
+use crate::db_datatime::{
+    get_current_datetime_utc, to_datetime_east_8, to_datetime_with_timezone_east_8,
+};
+use crate::token::AccessToken;
+use async_trait::async_trait;
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::{Keyword, SimpleExpr};
+use sea_orm::{IntoActiveModel, Select, UpdateMany};
+use std::future::Future;
+
+pub use webbase_macros::Audited;
+
+tokio::task_local! {
+    static CURRENT_ACTOR: AccessToken;
+}
+
+/// 当前操作者，由AuditActor中间件或with_actor设置，用于填充created_by和updated_by
+pub fn current_actor() -> Option<AccessToken> {
+    CURRENT_ACTOR.try_with(|access| access.clone()).ok()
+}
+
+/// 以access为操作者执行f，用于后台任务等没有经过AuditActor中间件的场景，
+/// tokio::spawn的任务不会继承当前的操作者，需要在任务中重新设置
+pub async fn with_actor<F: Future>(access: AccessToken, f: F) -> F::Output {
+    CURRENT_ACTOR.scope(access, f).await
+}
+
+/// created_at、updated_at和deleted_at字段可用的时间类型，精确到秒
+/// - DateTime: 东八区时间，与get_current_datetime一致
+/// - DateTimeUtc、DateTimeWithTimeZone及对应的Option
+pub trait AuditTime {
+    fn audit_time(now: &DateTimeUtc) -> Self;
+}
+
+impl AuditTime for DateTime {
+    fn audit_time(now: &DateTimeUtc) -> Self {
+        to_datetime_east_8(now)
+    }
+}
+
+impl AuditTime for DateTimeUtc {
+    fn audit_time(now: &DateTimeUtc) -> Self {
+        *now
+    }
+}
+
+impl AuditTime for DateTimeWithTimeZone {
+    fn audit_time(now: &DateTimeUtc) -> Self {
+        to_datetime_with_timezone_east_8(now)
+    }
+}
+
+impl<T: AuditTime> AuditTime for Option<T> {
+    fn audit_time(now: &DateTimeUtc) -> Self {
+        Some(T::audit_time(now))
+    }
+}
+
+/// created_by和updated_by字段可用的类型，字符串为user_account，整数为user_id
+pub trait AuditActor {
+    fn audit_actor(access: &AccessToken) -> Self;
+}
+
+impl AuditActor for String {
+    fn audit_actor(access: &AccessToken) -> Self {
+        access.user_account.clone()
+    }
+}
+
+impl AuditActor for u64 {
+    fn audit_actor(access: &AccessToken) -> Self {
+        access.user_id
+    }
+}
+
+impl AuditActor for i64 {
+    fn audit_actor(access: &AccessToken) -> Self {
+        access.user_id as i64
+    }
+}
+
+impl<T: AuditActor> AuditActor for Option<T> {
+    fn audit_actor(access: &AccessToken) -> Self {
+        Some(T::audit_actor(access))
+    }
+}
+
+/// 带有deleted_at字段的实体，由#[derive(Audited)]实现，deleted_at为空的记录为未删除
+/// ```ignore
+/// let users = user::Entity::find_active().all(db).await?;
+/// user::Entity::soft_delete_many()
+///     .filter(user::Column::Id.is_in(ids))
+///     .exec(db)
+///     .await?;
+/// ```
+pub trait SoftDelete: EntityTrait {
+    fn deleted_at_column() -> Self::Column;
+
+    /// 当前时间，类型与deleted_at字段一致
+    fn deleted_at_value() -> Value;
+
+    /// 未删除的条件，可用于关联查询等
+    fn not_deleted() -> SimpleExpr {
+        Self::deleted_at_column().is_null()
+    }
+
+    fn find_active() -> Select<Self> {
+        Self::find().filter(Self::not_deleted())
+    }
+
+    fn find_deleted() -> Select<Self> {
+        Self::find().filter(Self::deleted_at_column().is_not_null())
+    }
+
+    fn find_active_by_id<T>(values: T) -> Select<Self>
+    where
+        T: Into<<Self::PrimaryKey as PrimaryKeyTrait>::ValueType>,
+    {
+        Self::find_by_id(values).filter(Self::not_deleted())
+    }
+
+    /// 批量软删除，不经过ActiveModelBehavior，不会更新updated_at和updated_by
+    fn soft_delete_many() -> UpdateMany<Self> {
+        Self::update_many()
+            .col_expr(
+                Self::deleted_at_column(),
+                SimpleExpr::Value(Self::deleted_at_value()),
+            )
+            .filter(Self::not_deleted())
+    }
+
+    /// 批量恢复，不经过ActiveModelBehavior，不会更新updated_at和updated_by
+    fn restore_many() -> UpdateMany<Self> {
+        Self::update_many()
+            .col_expr(
+                Self::deleted_at_column(),
+                SimpleExpr::Keyword(Keyword::Null),
+            )
+            .filter(Self::deleted_at_column().is_not_null())
+    }
+}
+
+/// 单条记录的软删除和恢复，经过ActiveModelBehavior，同时更新updated_at和updated_by
+/// ```ignore
+/// let user = user.into_active_model().soft_delete(db).await?;
+/// let user = user.into_active_model().restore(db).await?;
+/// ```
+#[async_trait]
+pub trait SoftDeleteActiveModel: ActiveModelTrait + ActiveModelBehavior + Send
+where
+    Self::Entity: SoftDelete,
+    <Self::Entity as EntityTrait>::Model: IntoActiveModel<Self>,
+{
+    async fn soft_delete<C>(mut self, db: &C) -> Result<<Self::Entity as EntityTrait>::Model, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        self.try_set(
+            <Self::Entity as SoftDelete>::deleted_at_column(),
+            <Self::Entity as SoftDelete>::deleted_at_value(),
+        )?;
+        self.update(db).await
+    }
+
+    async fn restore<C>(mut self, db: &C) -> Result<<Self::Entity as EntityTrait>::Model, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        self.try_set(
+            <Self::Entity as SoftDelete>::deleted_at_column(),
+            <Self::Entity as SoftDelete>::deleted_at_value().as_null(),
+        )?;
+        self.update(db).await
+    }
+}
+
+impl<A> SoftDeleteActiveModel for A
+where
+    A: ActiveModelTrait + ActiveModelBehavior + Send,
+    A::Entity: SoftDelete,
+    <A::Entity as EntityTrait>::Model: IntoActiveModel<A>,
+{
+}
+
+/// Audited宏生成的ActiveModelBehavior使用的当前时间和操作者
+#[doc(hidden)]
+pub fn audit_context() -> (DateTimeUtc, Option<AccessToken>) {
+    (get_current_datetime_utc(), current_actor())
+}
+
+#[cfg(test)]
+mod audit_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn actor_test() {
+        assert!(current_actor().is_none());
+        let access = AccessToken {
+            user_id: 7,
+            user_name: "张三".into(),
+            user_account: "zhangsan".into(),
+            app_id: "app".into(),
+            exp: 0,
+        };
+        let (by, id) = with_actor(access, async {
+            let actor = current_actor().unwrap();
+            (
+                <Option<String>>::audit_actor(&actor),
+                <i64>::audit_actor(&actor),
+            )
+        })
+        .await;
+        assert_eq!(by.as_deref(), Some("zhangsan"));
+        assert_eq!(id, 7);
+        assert!(current_actor().is_none());
+
+        let now = get_current_datetime_utc();
+        assert_eq!(
+            DateTimeWithTimeZone::audit_time(&now).naive_local(),
+            DateTime::audit_time(&now)
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    mod article {
+        use super::Audited;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Audited)]
+        #[sea_orm(table_name = "article")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub title: String,
+            pub created_at: DateTime,
+            pub created_by: Option<String>,
+            pub updated_at: DateTimeUtc,
+            pub updated_by: Option<i64>,
+            pub deleted_at: Option<DateTime>,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn audited_sqlite_test() {
+        use sea_orm::{ActiveValue::Set, IntoActiveModel};
+
+        let db = crate::db::DbConfig::new("sqlite::memory:")
+            .connect()
+            .await
+            .unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE article (id INTEGER PRIMARY KEY, title TEXT NOT NULL, \
+             created_at TIMESTAMP NOT NULL, created_by TEXT, updated_at TIMESTAMP NOT NULL, \
+             updated_by INTEGER, deleted_at TIMESTAMP)",
+        )
+        .await
+        .unwrap();
+        let actor = |user_id: u64, user_account: &str| AccessToken {
+            user_id,
+            user_name: user_account.into(),
+            user_account: user_account.into(),
+            app_id: "app".into(),
+            exp: 0,
+        };
+
+        let inserted = with_actor(actor(1, "creator"), async {
+            article::ActiveModel {
+                title: Set("标题".into()),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+        })
+        .await
+        .unwrap();
+        assert_eq!(inserted.created_by.as_deref(), Some("creator"));
+        assert_eq!(inserted.updated_by, Some(1));
+        assert!(inserted.deleted_at.is_none());
+
+        let mut model = inserted.clone().into_active_model();
+        model.title = Set("新标题".into());
+        let updated = with_actor(actor(2, "editor"), model.update(&db))
+            .await
+            .unwrap();
+        assert_eq!(updated.created_at, inserted.created_at);
+        assert_eq!(updated.created_by.as_deref(), Some("creator"));
+        assert_eq!(updated.updated_by, Some(2));
+
+        let deleted = with_actor(
+            actor(3, "deleter"),
+            updated.into_active_model().soft_delete(&db),
+        )
+        .await
+        .unwrap();
+        assert!(deleted.deleted_at.is_some());
+        assert_eq!(deleted.updated_by, Some(3));
+        assert!(article::Entity::find_active()
+            .one(&db)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(article::Entity::find_deleted().count(&db).await.unwrap(), 1);
+
+        // 没有操作者时不修改updated_by
+        let restored = deleted.into_active_model().restore(&db).await.unwrap();
+        assert!(restored.deleted_at.is_none());
+        assert_eq!(restored.updated_by, Some(3));
+        assert!(article::Entity::find_active_by_id(restored.id)
+            .one(&db)
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,344 @@
+use actix_web::{dev::Payload, FromRequest, HttpMessage, HttpRequest};
+use async_trait::async_trait;
+use sea_orm::{
+    AccessMode, ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbBackend, DbErr,
+    ExecResult, IsolationLevel, QueryResult, Statement, TransactionError, TransactionTrait,
+};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+struct Replica {
+    conn: DatabaseConnection,
+    healthy: AtomicBool,
+}
+
+/// 读写分离，写操作和事务使用主库，只读查询按轮询使用健康的从库，没有健康的从库时使用主库
+/// ```ignore
+/// let router = DbRouter::from_registry(db::PRIMARY, &["replica1", "replica2"])?;
+/// init_db_router(router).start_health_check(Duration::from_secs(10));
+///
+/// async fn handler(db: RoutedConnection) -> Response<..> {
+///     Entity::find().all(&db).await?; // 从库
+/// }
+/// ```
+pub struct DbRouter {
+    primary: DatabaseConnection,
+    replicas: Vec<Replica>,
+    next: AtomicUsize,
+}
+
+impl DbRouter {
+    pub fn new(primary: DatabaseConnection, replicas: Vec<DatabaseConnection>) -> Self {
+        Self {
+            primary,
+            replicas: replicas
+                .into_iter()
+                .map(|conn| Replica {
+                    conn,
+                    healthy: AtomicBool::new(true),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// 使用db::init_database注册的连接
+    pub fn from_registry(primary: &str, replicas: &[&str]) -> anyhow::Result<Self> {
+        let get = |name: &str| {
+            super::try_get_database(name).ok_or(anyhow::anyhow!("数据库{}未初始化", name))
+        };
+        Ok(Self::new(
+            get(primary)?,
+            replicas
+                .iter()
+                .map(|name| get(name))
+                .collect::<anyhow::Result<_>>()?,
+        ))
+    }
+
+    pub fn primary(&self) -> &DatabaseConnection {
+        &self.primary
+    }
+
+    /// 按轮询返回健康的从库，都不可用时返回主库
+    pub fn replica(&self) -> &DatabaseConnection {
+        self.replica_index()
+            .map(|index| &self.replicas[index].conn)
+            .unwrap_or(&self.primary)
+    }
+
+    fn replica_index(&self) -> Option<usize> {
+        let len = self.replicas.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|index| self.replicas[*index].healthy.load(Ordering::Relaxed))
+    }
+
+    /// 可用的从库数量
+    pub fn healthy_replicas(&self) -> usize {
+        self.replicas
+            .iter()
+            .filter(|replica| replica.healthy.load(Ordering::Relaxed))
+            .count()
+    }
+
+    /// 定时ping从库，恢复后重新使用
+    pub fn start_health_check(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let router = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                for (index, replica) in router.replicas.iter().enumerate() {
+                    let healthy = replica.conn.ping().await.is_ok();
+                    if replica.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+                        tracing::warn!("db replica {} healthy: {}", index, healthy);
+                    }
+                }
+            }
+        })
+    }
+
+    /// 新的请求级连接，同一请求中使用同一个RoutedConnection才能读到自己的写入
+    pub fn connection(self: &Arc<Self>) -> RoutedConnection {
+        RoutedConnection {
+            router: self.clone(),
+            wrote: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+static DBROUTER: OnceLock<Arc<DbRouter>> = OnceLock::<Arc<DbRouter>>::new();
+
+pub fn init_db_router(router: DbRouter) -> &'static Arc<DbRouter> {
+    DBROUTER.get_or_init(|| Arc::new(router))
+}
+
+pub fn get_db_router() -> &'static Arc<DbRouter> {
+    DBROUTER.get().unwrap()
+}
+
+pub fn try_get_db_router() -> Option<&'static Arc<DbRouter>> {
+    DBROUTER.get()
+}
+
+/// 实现ConnectionTrait的连接，只读查询使用从库，执行过写操作或开启过事务后都使用主库
+#[derive(Clone)]
+pub struct RoutedConnection {
+    router: Arc<DbRouter>,
+    wrote: Arc<AtomicBool>,
+}
+
+impl RoutedConnection {
+    /// 之后的查询都使用主库
+    pub fn use_primary(&self) {
+        self.wrote.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.wrote.load(Ordering::Relaxed)
+    }
+
+    fn writer(&self) -> &DatabaseConnection {
+        self.use_primary();
+        &self.router.primary
+    }
+
+    /// 只读语句返回可用的从库序号
+    fn reader(&self, stmt: &Statement) -> Option<usize> {
+        if self.is_primary() || !is_read_only(&stmt.sql) {
+            return None;
+        }
+        self.router.replica_index()
+    }
+
+    async fn query<T, F, Fut>(&self, stmt: Statement, f: F) -> Result<T, DbErr>
+    where
+        F: Fn(DatabaseConnection, Statement) -> Fut,
+        Fut: Future<Output = Result<T, DbErr>>,
+    {
+        let Some(index) = self.reader(&stmt) else {
+            return f(self.writer().clone(), stmt).await;
+        };
+        let replica = &self.router.replicas[index];
+        match f(replica.conn.clone(), stmt.clone()).await {
+            // 从库连接失败时标记为不可用并改用主库，由健康检查恢复
+            Err(DbErr::Conn(e)) => {
+                tracing::warn!("db replica {} unavailable: {:?}", index, e);
+                replica.healthy.store(false, Ordering::Relaxed);
+                f(self.router.primary.clone(), stmt).await
+            }
+            Err(DbErr::ConnectionAcquire(e)) => {
+                tracing::warn!("db replica {} unavailable: {:?}", index, e);
+                replica.healthy.store(false, Ordering::Relaxed);
+                f(self.router.primary.clone(), stmt).await
+            }
+            res => res,
+        }
+    }
+}
+
+/// SELECT等只读语句使用从库，以下语句使用主库
+/// - 加锁读: FOR UPDATE、FOR NO KEY UPDATE、FOR SHARE、FOR KEY SHARE、LOCK IN SHARE MODE
+/// - 包含修改数据的WITH语句，如postgres的WITH x AS (DELETE ... RETURNING ...) SELECT ...
+/// - 分析修改数据语句的EXPLAIN ANALYZE，postgres会实际执行被分析的语句
+fn is_read_only(sql: &str) -> bool {
+    let sql = sql.to_ascii_uppercase();
+    let words = sql
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    let Some(&first) = words.first() else {
+        return false;
+    };
+    if !["SELECT", "WITH", "SHOW", "EXPLAIN", "DESCRIBE"].contains(&first) {
+        return false;
+    }
+    let has_write = || {
+        words
+            .iter()
+            .any(|word| ["INSERT", "UPDATE", "DELETE", "MERGE"].contains(word))
+    };
+    if first == "WITH" && has_write() {
+        return false;
+    }
+    if first == "EXPLAIN" && words.contains(&"ANALYZE") && has_write() {
+        return false;
+    }
+    let locking = words
+        .windows(2)
+        .any(|pair| pair[0] == "FOR" && matches!(pair[1], "UPDATE" | "SHARE" | "NO" | "KEY"))
+        || words.windows(3).any(|each| each == ["LOCK", "IN", "SHARE"]);
+    !locking
+}
+
+#[async_trait]
+impl ConnectionTrait for RoutedConnection {
+    fn get_database_backend(&self) -> DbBackend {
+        self.router.primary.get_database_backend()
+    }
+
+    async fn execute(&self, stmt: Statement) -> Result<ExecResult, DbErr> {
+        self.writer().execute(stmt).await
+    }
+
+    async fn execute_unprepared(&self, sql: &str) -> Result<ExecResult, DbErr> {
+        self.writer().execute_unprepared(sql).await
+    }
+
+    async fn query_one(&self, stmt: Statement) -> Result<Option<QueryResult>, DbErr> {
+        self.query(stmt, |conn, stmt| async move { conn.query_one(stmt).await })
+            .await
+    }
+
+    async fn query_all(&self, stmt: Statement) -> Result<Vec<QueryResult>, DbErr> {
+        self.query(stmt, |conn, stmt| async move { conn.query_all(stmt).await })
+            .await
+    }
+}
+
+#[async_trait]
+impl TransactionTrait for RoutedConnection {
+    async fn begin(&self) -> Result<DatabaseTransaction, DbErr> {
+        self.writer().begin().await
+    }
+
+    async fn begin_with_config(
+        &self,
+        isolation_level: Option<IsolationLevel>,
+        access_mode: Option<AccessMode>,
+    ) -> Result<DatabaseTransaction, DbErr> {
+        self.writer()
+            .begin_with_config(isolation_level, access_mode)
+            .await
+    }
+
+    async fn transaction<F, T, E>(&self, callback: F) -> Result<T, TransactionError<E>>
+    where
+        F: for<'c> FnOnce(
+                &'c DatabaseTransaction,
+            ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>
+            + Send,
+        T: Send,
+        E: std::fmt::Display + std::fmt::Debug + Send,
+    {
+        self.writer().transaction(callback).await
+    }
+
+    async fn transaction_with_config<F, T, E>(
+        &self,
+        callback: F,
+        isolation_level: Option<IsolationLevel>,
+        access_mode: Option<AccessMode>,
+    ) -> Result<T, TransactionError<E>>
+    where
+        F: for<'c> FnOnce(
+                &'c DatabaseTransaction,
+            ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>
+            + Send,
+        T: Send,
+        E: std::fmt::Display + std::fmt::Debug + Send,
+    {
+        self.writer()
+            .transaction_with_config(callback, isolation_level, access_mode)
+            .await
+    }
+}
+
+/// 同一请求中多次提取得到共享写入状态的连接，使用init_db_router初始化的DbRouter
+impl FromRequest for RoutedConnection {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Some(conn) = req.extensions().get::<RoutedConnection>() {
+            return ready(Ok(conn.clone()));
+        }
+        let Some(router) = try_get_db_router() else {
+            return ready(Err(actix_web::error::ErrorInternalServerError(
+                "DbRouter未初始化",
+            )));
+        };
+        let conn = router.connection();
+        req.extensions_mut().insert(conn.clone());
+        ready(Ok(conn))
+    }
+}
+
+#[cfg(test)]
+mod router_tests {
+    use super::*;
+
+    #[test]
+    fn read_only_test() {
+        assert!(is_read_only("  select * from user"));
+        assert!(is_read_only("WITH t AS (SELECT 1) SELECT * FROM t"));
+        assert!(!is_read_only(
+            "WITH x AS (DELETE FROM t RETURNING id) SELECT * FROM x"
+        ));
+        assert!(!is_read_only(
+            "with x as (update t set a = 1 returning id) select * from x"
+        ));
+        assert!(is_read_only(
+            "WITH t AS (SELECT updated_at FROM t) SELECT * FROM t"
+        ));
+        assert!(!is_read_only("SELECT * FROM user WHERE id = 1 FOR UPDATE"));
+        assert!(!is_read_only("SELECT * FROM user WHERE id = 1\nFOR UPDATE"));
+        assert!(!is_read_only("SELECT * FROM user FOR NO KEY UPDATE"));
+        assert!(!is_read_only("select * from user for key share"));
+        assert!(!is_read_only("SELECT * FROM user LOCK IN SHARE MODE"));
+        assert!(!is_read_only("EXPLAIN ANALYZE DELETE FROM user"));
+        assert!(!is_read_only(
+            "EXPLAIN (ANALYZE, BUFFERS) UPDATE user SET name = 'a'"
+        ));
+        assert!(is_read_only("EXPLAIN ANALYZE SELECT * FROM user"));
+        assert!(is_read_only("EXPLAIN DELETE FROM user"));
+        assert!(is_read_only("SELECT * FROM user_for_update"));
+        assert!(!is_read_only("INSERT INTO user (name) VALUES ('a')"));
+        assert!(!is_read_only("UPDATE user SET name = 'a'"));
+    }
+}
@@ -0,0 +1,256 @@
+use sea_orm::{
+    AccessMode, DatabaseTransaction, DbErr, IsolationLevel, RuntimeErr, TransactionTrait,
+};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// with_transaction的事务回调，返回装箱的future
+/// - `|txn| Box::pin(async move { ... })`
+pub type TransactionFuture<'c, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'c>>;
+
+/// 事务的隔离级别、访问模式和重试策略
+#[derive(Clone, Debug)]
+pub struct TransactionOptions {
+    pub isolation_level: Option<IsolationLevel>,
+    pub access_mode: Option<AccessMode>,
+    pub max_retries: u32,  // 死锁等可重试错误的最大重试次数
+    pub backoff: Duration, // 第一次重试的等待时间，之后每次翻倍
+}
+
+impl Default for TransactionOptions {
+    fn default() -> Self {
+        Self {
+            isolation_level: None,
+            access_mode: None,
+            max_retries: 3,
+            backoff: Duration::from_millis(50),
+        }
+    }
+}
+
+impl TransactionOptions {
+    pub fn with_isolation_level(mut self, isolation_level: IsolationLevel) -> Self {
+        self.isolation_level = Some(isolation_level);
+        self
+    }
+
+    pub fn with_access_mode(mut self, access_mode: AccessMode) -> Self {
+        self.access_mode = Some(access_mode);
+        self
+    }
+
+    pub fn with_retry(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.backoff = backoff;
+        self
+    }
+}
+
+/// 在事务中执行f，返回Ok时提交，返回Err时回滚，死锁或锁等待超时时按退避时间重新执行整个事务，
+/// f可能执行多次，事务外的副作用需要保证幂等
+/// ```ignore
+/// let user = with_transaction(get_mysql(), |txn| {
+///     Box::pin(async move {
+///         let user = user::ActiveModel { .. }.insert(txn).await?;
+///         Ok(user)
+///     })
+/// })
+/// .await?;
+/// ```
+pub async fn with_transaction<C, F, T>(conn: &C, f: F) -> anyhow::Result<T>
+where
+    C: TransactionTrait,
+    F: for<'c> FnMut(&'c DatabaseTransaction) -> TransactionFuture<'c, T>,
+{
+    with_transaction_options(conn, TransactionOptions::default(), f).await
+}
+
+pub async fn with_transaction_options<C, F, T>(
+    conn: &C,
+    options: TransactionOptions,
+    mut f: F,
+) -> anyhow::Result<T>
+where
+    C: TransactionTrait,
+    F: for<'c> FnMut(&'c DatabaseTransaction) -> TransactionFuture<'c, T>,
+{
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+    let mut attempts = 0;
+    loop {
+        let res = run_once(conn, &options, &mut f).await;
+        match res {
+            Err(e) if attempts < options.max_retries && is_retryable_error(&e) => {
+                attempts += 1;
+                tracing::warn!("transaction retry {} after error: {:?}", attempts, e);
+                #[cfg(feature = "metrics")]
+                crate::metrics::record_db_transaction("retry", None);
+                tokio::time::sleep(options.backoff * 2u32.saturating_pow(attempts - 1)).await;
+            }
+            res => {
+                #[cfg(feature = "metrics")]
+                crate::metrics::record_db_transaction(
+                    if res.is_ok() { "commit" } else { "rollback" },
+                    Some(start),
+                );
+                return res;
+            }
+        }
+    }
+}
+
+async fn run_once<C, F, T>(conn: &C, options: &TransactionOptions, f: &mut F) -> anyhow::Result<T>
+where
+    C: TransactionTrait,
+    F: for<'c> FnMut(&'c DatabaseTransaction) -> TransactionFuture<'c, T>,
+{
+    let txn = conn
+        .begin_with_config(options.isolation_level, options.access_mode)
+        .await?;
+    match f(&txn).await {
+        Ok(value) => {
+            txn.commit().await?;
+            Ok(value)
+        }
+        Err(e) => {
+            if let Err(rollback) = txn.rollback().await {
+                tracing::warn!("transaction rollback error: {:?}", rollback);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// 在事务中创建savepoint执行f，返回Err时只回滚到savepoint，外层事务可以继续，不重试
+pub async fn with_savepoint<F, T>(txn: &DatabaseTransaction, f: F) -> anyhow::Result<T>
+where
+    F: for<'c> FnOnce(&'c DatabaseTransaction) -> TransactionFuture<'c, T>,
+{
+    let savepoint = txn.begin().await?;
+    match f(&savepoint).await {
+        Ok(value) => {
+            savepoint.commit().await?;
+            Ok(value)
+        }
+        Err(e) => {
+            // 回滚失败时savepoint随外层事务一起回滚，返回f的错误
+            if let Err(rollback) = savepoint.rollback().await {
+                tracing::warn!("rollback savepoint error: {:?}", rollback);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// 死锁、锁等待超时、序列化失败等重新执行事务可能成功的错误
+/// - mysql: 1213死锁，1205锁等待超时
+/// - postgres: 40001序列化失败，40P01死锁
+/// - sqlite: 主错误码5 SQLITE_BUSY，6 SQLITE_LOCKED，包括SQLITE_BUSY_SNAPSHOT等扩展错误码
+pub fn is_retryable_error(err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<DbErr>() {
+        Some(DbErr::Exec(e)) | Some(DbErr::Query(e)) | Some(DbErr::Conn(e)) => {
+            is_retryable_runtime_error(e)
+        }
+        _ => false,
+    }
+}
+
+#[allow(unused_variables)]
+fn is_retryable_runtime_error(err: &RuntimeErr) -> bool {
+    #[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+    if let RuntimeErr::SqlxError(sea_orm::sqlx::Error::Database(e)) = err {
+        #[cfg(feature = "mysql")]
+        if let Some(e) = e.try_downcast_ref::<sea_orm::sqlx::mysql::MySqlDatabaseError>() {
+            return matches!(e.number(), 1213 | 1205);
+        }
+        #[cfg(feature = "sqlite")]
+        if e.try_downcast_ref::<sea_orm::sqlx::sqlite::SqliteError>()
+            .is_some()
+        {
+            return e.code().is_some_and(|code| is_sqlite_busy_code(&code));
+        }
+        return matches!(e.code().as_deref(), Some("40001" | "40P01"));
+    }
+    false
+}
+
+/// sqlx返回的是扩展错误码，低8位为主错误码
+#[cfg(feature = "sqlite")]
+fn is_sqlite_busy_code(code: &str) -> bool {
+    code.parse::<i32>()
+        .is_ok_and(|code| matches!(code & 0xff, 5 | 6))
+}
+
+#[cfg(test)]
+mod transaction_tests {
+    use super::*;
+
+    #[test]
+    fn retryable_test() {
+        assert!(!is_retryable_error(&anyhow::anyhow!("deadlock")));
+        assert!(!is_retryable_error(
+            &DbErr::RecordNotFound("user".into()).into()
+        ));
+        #[cfg(feature = "sqlite")]
+        {
+            assert!(is_sqlite_busy_code("5"));
+            assert!(is_sqlite_busy_code("517")); // SQLITE_BUSY_SNAPSHOT
+            assert!(is_sqlite_busy_code("262")); // SQLITE_LOCKED_SHAREDCACHE
+            assert!(!is_sqlite_busy_code("19"));
+            assert!(!is_sqlite_busy_code("40001"));
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_transaction_test() {
+        use sea_orm::{ConnectionTrait, DbBackend, Statement};
+        let conn = super::super::DbConfig::new("sqlite::memory:")
+            .connect()
+            .await
+            .unwrap();
+        conn.execute_unprepared("CREATE TABLE t (id INTEGER PRIMARY KEY)")
+            .await
+            .unwrap();
+        let res: anyhow::Result<()> = with_transaction(&conn, |txn| {
+            Box::pin(async move {
+                txn.execute_unprepared("INSERT INTO t (id) VALUES (1)")
+                    .await?;
+                Err(anyhow::anyhow!("rollback"))
+            })
+        })
+        .await;
+        assert!(res.is_err());
+        with_transaction(&conn, |txn| {
+            Box::pin(async move {
+                txn.execute_unprepared("INSERT INTO t (id) VALUES (2)")
+                    .await?;
+                let res: anyhow::Result<()> = with_savepoint(txn, |txn| {
+                    Box::pin(async move {
+                        txn.execute_unprepared("INSERT INTO t (id) VALUES (3)")
+                            .await?;
+                        Err(anyhow::anyhow!("rollback savepoint"))
+                    })
+                })
+                .await;
+                assert!(res.is_err());
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+        let rows = conn
+            .query_all(Statement::from_string(
+                DbBackend::Sqlite,
+                "SELECT id FROM t",
+            ))
+            .await
+            .unwrap();
+        let ids: Vec<i32> = rows
+            .iter()
+            .map(|row| row.try_get("", "id").unwrap())
+            .collect();
+        assert_eq!(ids, vec![2]);
+    }
+}
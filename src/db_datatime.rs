@@ -36,6 +36,27 @@ pub fn get_current_datetime() -> DateTime {
     DateTime::parse_from_str(&dt, "%Y-%m-%d %H:%M:%S").unwrap()
 }
 
+/// 精确到秒的UTC时间，可用于mysql的DATETIME/TIMESTAMP、postgres的TIMESTAMPTZ和sqlite，
+/// 精确到秒避免mysql的DATETIME舍入后与写入的值不一致
+pub fn get_current_datetime_utc() -> DateTimeUtc {
+    let now = get_current_time();
+    now - chrono::Duration::nanoseconds(now.timestamp_subsec_nanos() as i64)
+}
+
+/// 精确到秒的东八区时间，对应DateTimeWithTimeZone类型的字段
+pub fn get_current_datetime_with_timezone() -> DateTimeWithTimeZone {
+    to_datetime_with_timezone_east_8(&get_current_datetime_utc())
+}
+
+/// UTC时间转为东八区的DateTime，与get_current_datetime的取值方式一致
+pub fn to_datetime_east_8(utc: &DateTimeUtc) -> DateTime {
+    from_utc_to_east_8(utc).naive_local()
+}
+
+pub fn to_datetime_with_timezone_east_8(utc: &DateTimeUtc) -> DateTimeWithTimeZone {
+    from_utc_to_east_8(utc)
+}
+
 pub fn get_current_day_str() -> String {
     get_current_time_east_8().format("%Y-%m-%d").to_string()
 }
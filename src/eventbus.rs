@@ -0,0 +1,304 @@
+use crate::redisfred::{
+    connect_blocking_client, ensure_stream_group, quit_in_background, StreamEntry,
+};
+use fred::clients::{RedisClient, RedisPool, SubscriberClient};
+use fred::interfaces::{EventInterface, PubsubInterface, StreamsInterface};
+use futures::Stream;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+/// 订阅得到的消息流，无法反序列化的消息会被跳过
+pub type EventStream<T> = Pin<Box<dyn Stream<Item = T> + Send>>;
+
+/// 基于redisfred连接池的事件总线
+/// - publish/subscribe: redis pub/sub，只有在线的订阅者能收到，适合缓存失效等广播通知
+/// - append/consumer: redis stream和消费组，至少投递一次，需要ack，适合领域事件
+#[derive(Clone)]
+pub struct EventBus {
+    redis: RedisPool,
+}
+
+impl EventBus {
+    pub fn new(redis: RedisPool) -> Self {
+        Self { redis }
+    }
+
+    /// 使用redisfred::init_redis_pool初始化的连接池
+    pub fn fred() -> Self {
+        Self::new(crate::redisfred::get_redis_pool().pool.clone())
+    }
+
+    /// 以JSON格式发布消息，返回收到消息的订阅者数量
+    pub async fn publish<T: Serialize>(&self, channel: &str, msg: &T) -> anyhow::Result<i64> {
+        let data = serde_json::to_string(msg)?;
+        Ok(self.redis.next().publish(channel, data).await?)
+    }
+
+    /// 订阅频道，pattern含有`*`、`?`或`[`时按模式订阅，断线重连后自动重新订阅，
+    /// 丢弃返回的流时取消订阅并关闭连接
+    pub async fn subscribe<T>(&self, pattern: &str) -> anyhow::Result<EventStream<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let subscriber = crate::redisfred::new_subscriber_client_from(self.redis.next()).await?;
+        if is_pattern(pattern) {
+            subscriber.psubscribe(pattern).await?;
+        } else {
+            subscriber.subscribe(pattern).await?;
+        }
+        let rx = subscriber.message_rx();
+        let guard = SubscriberGuard(subscriber);
+        Ok(Box::pin(futures::stream::unfold(
+            (rx, guard),
+            |(mut rx, guard)| async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(message) => {
+                            let Some(data) = message.value.as_str() else {
+                                continue;
+                            };
+                            match serde_json::from_str::<T>(&data) {
+                                Ok(msg) => return Some((msg, (rx, guard))),
+                                Err(e) => tracing::warn!(
+                                    "event bus decode message from {} error: {:?}",
+                                    message.channel,
+                                    e
+                                ),
+                            }
+                        }
+                        Err(RecvError::Lagged(count)) => {
+                            tracing::warn!("event bus subscriber lagged {} messages", count)
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            },
+        )))
+    }
+
+    /// 以JSON格式追加到stream，max_len不为空时近似裁剪到该长度，返回消息id
+    pub async fn append<T: Serialize>(
+        &self,
+        stream: &str,
+        msg: &T,
+        max_len: Option<u64>,
+    ) -> anyhow::Result<String> {
+        let data = serde_json::to_string(msg)?;
+        let fields = vec![("data", data)];
+        Ok(match max_len {
+            Some(max_len) => {
+                self.redis
+                    .xadd(stream, false, ("MAXLEN", "~", max_len as i64), "*", fields)
+                    .await?
+            }
+            None => self.redis.xadd(stream, false, None, "*", fields).await?,
+        })
+    }
+
+    /// 创建stream的消费者，消费组不存在时从stream开头创建
+    pub async fn consumer<T>(
+        &self,
+        stream: &str,
+        group: &str,
+        consumer: &str,
+    ) -> anyhow::Result<StreamConsumer<T>>
+    where
+        T: DeserializeOwned,
+    {
+        ensure_stream_group(&self.redis, stream, group).await?;
+        let reader = connect_blocking_client(&self.redis).await?;
+        Ok(StreamConsumer {
+            redis: self.redis.clone(),
+            reader,
+            stream: stream.to_string(),
+            group: group.to_string(),
+            consumer: consumer.to_string(),
+            pending_cursor: Some("0".into()),
+            _payload: PhantomData,
+        })
+    }
+}
+
+fn is_pattern(channel: &str) -> bool {
+    channel.contains(['*', '?', '['])
+}
+
+/// append写入的data字段
+fn decode_fields<T: DeserializeOwned>(fields: &HashMap<String, String>) -> anyhow::Result<T> {
+    let data = fields
+        .get("data")
+        .ok_or_else(|| anyhow::anyhow!("missing data field"))?;
+    Ok(serde_json::from_str(data)?)
+}
+
+struct SubscriberGuard(SubscriberClient);
+
+impl Drop for SubscriberGuard {
+    fn drop(&mut self) {
+        quit_in_background(&self.0);
+    }
+}
+
+/// stream中的一条消息，处理完成后需要调用StreamConsumer::ack
+#[derive(Clone, Debug)]
+pub struct Delivery<T> {
+    pub id: String,
+    pub payload: T,
+}
+
+/// 消费组中的一个消费者，未ack的消息在重启后会重新投递给同名消费者，
+/// 也可以通过claim_idle由其他消费者接管
+pub struct StreamConsumer<T> {
+    redis: RedisPool,
+    reader: RedisClient,
+    pub stream: String,
+    pub group: String,
+    pub consumer: String,
+    pending_cursor: Option<String>, // 读取上次未ack的消息的位置，读取完后为None
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> StreamConsumer<T> {
+    /// 读取最多count条消息，首先返回本消费者上次未ack的消息，没有新消息时最多等待block
+    pub async fn recv(&mut self, count: u64, block: Duration) -> anyhow::Result<Vec<Delivery<T>>> {
+        if let Some(cursor) = self.pending_cursor.clone() {
+            let messages = self.read(&cursor, count, None).await?;
+            self.pending_cursor = messages.last().map(|(id, _)| id.clone());
+            if !messages.is_empty() {
+                return self.decode(messages).await;
+            }
+        }
+        let messages = self
+            .read(">", count, Some(block.as_millis() as u64))
+            .await?;
+        self.decode(messages).await
+    }
+
+    /// 接管其他消费者超过min_idle未ack的消息
+    pub async fn claim_idle(
+        &self,
+        min_idle: Duration,
+        count: u64,
+    ) -> anyhow::Result<Vec<Delivery<T>>> {
+        let (_, messages): (String, Vec<StreamEntry>) = self
+            .redis
+            .xautoclaim_values(
+                self.stream.as_str(),
+                self.group.as_str(),
+                self.consumer.as_str(),
+                min_idle.as_millis() as u64,
+                "0-0",
+                Some(count),
+                false,
+            )
+            .await?;
+        self.decode(messages).await
+    }
+
+    pub async fn ack(&self, id: &str) -> anyhow::Result<()> {
+        let _: i64 = self
+            .redis
+            .xack(self.stream.as_str(), self.group.as_str(), id)
+            .await?;
+        Ok(())
+    }
+
+    async fn read(
+        &self,
+        id: &str,
+        count: u64,
+        block: Option<u64>,
+    ) -> anyhow::Result<Vec<StreamEntry>> {
+        let messages: HashMap<String, Vec<StreamEntry>> = self
+            .reader
+            .xreadgroup_map(
+                self.group.as_str(),
+                self.consumer.as_str(),
+                Some(count),
+                block,
+                false,
+                self.stream.as_str(),
+                id,
+            )
+            .await?;
+        Ok(messages.into_values().flatten().collect())
+    }
+
+    /// 无法反序列化的消息记录日志后直接ack，避免反复投递
+    async fn decode(&self, messages: Vec<StreamEntry>) -> anyhow::Result<Vec<Delivery<T>>> {
+        let mut deliveries = Vec::with_capacity(messages.len());
+        for (id, fields) in messages {
+            match decode_fields::<T>(&fields) {
+                Ok(payload) => deliveries.push(Delivery { id, payload }),
+                Err(e) => {
+                    tracing::error!("event bus decode {}:{} error: {:?}", self.stream, id, e);
+                    self.ack(&id).await?;
+                }
+            }
+        }
+        Ok(deliveries)
+    }
+}
+
+impl<T> Drop for StreamConsumer<T> {
+    fn drop(&mut self) {
+        quit_in_background(&self.reader);
+    }
+}
+
+#[cfg(test)]
+mod eventbus_tests {
+    use super::*;
+    use fred::interfaces::{ClientLike, KeysInterface};
+
+    #[test]
+    fn decode_test() {
+        assert!(is_pattern("order.*"));
+        assert!(!is_pattern("order.created"));
+
+        let fields = HashMap::from([("data".to_string(), r#"{"id":1}"#.to_string())]);
+        let msg: serde_json::Value = decode_fields(&fields).unwrap();
+        assert_eq!(msg["id"], 1);
+        assert!(decode_fields::<serde_json::Value>(&HashMap::new()).is_err());
+        let fields = HashMap::from([("data".to_string(), "{".to_string())]);
+        assert!(decode_fields::<serde_json::Value>(&fields).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "需要redis，设置REDIS_URL后使用--ignored运行"]
+    async fn stream_test() {
+        let url = std::env::var("REDIS_URL").unwrap();
+        let redis = RedisPool::new(
+            fred::types::RedisConfig::from_url(&url).unwrap(),
+            None,
+            None,
+            None,
+            1,
+        )
+        .unwrap();
+        redis.init().await.unwrap();
+        let bus = EventBus::new(redis.clone());
+        let stream = format!("eventbus_test:{}", uuid::Uuid::new_v4().simple());
+        let mut consumer = bus.consumer::<u64>(&stream, "g", "c1").await.unwrap();
+        bus.append(&stream, &1u64, None).await.unwrap();
+        let _: String = redis
+            .xadd(stream.as_str(), false, None, "*", vec![("data", "x")])
+            .await
+            .unwrap();
+
+        // 无法反序列化的消息被ack，不会返回
+        let deliveries = consumer.recv(10, Duration::from_millis(100)).await.unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].payload, 1);
+        // 未ack的消息可以被其他消费者接管
+        let other = bus.consumer::<u64>(&stream, "g", "c2").await.unwrap();
+        let claimed = other.claim_idle(Duration::ZERO, 10).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        other.ack(&claimed[0].id).await.unwrap();
+        let _: i64 = redis.del(stream.as_str()).await.unwrap();
+    }
+}
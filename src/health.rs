@@ -0,0 +1,350 @@
+use crate::{NoneBodyData, Response};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, Scope};
+use async_trait::async_trait;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// 依赖服务的健康检查
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    fn name(&self) -> String;
+    async fn check(&self) -> anyhow::Result<()>;
+}
+
+/// 使用init_mysql初始化的连接
+pub struct MysqlHealth;
+
+#[async_trait]
+impl HealthCheck for MysqlHealth {
+    fn name(&self) -> String {
+        "mysql".into()
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        let conn = crate::mysql::try_get_mysql().ok_or(anyhow::anyhow!("mysql未初始化"))?;
+        conn.ping().await?;
+        Ok(())
+    }
+}
+
+/// 使用db::init_database注册的连接
+pub struct DatabaseHealth {
+    pub name: String,
+}
+
+impl DatabaseHealth {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for DatabaseHealth {
+    fn name(&self) -> String {
+        format!("db:{}", self.name)
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        let conn = crate::db::try_get_database(&self.name)
+            .ok_or(anyhow::anyhow!("数据库{}未初始化", self.name))?;
+        conn.ping().await?;
+        Ok(())
+    }
+}
+
+/// 使用redis::init_redis_pool初始化的连接
+#[cfg(feature = "redis-rs")]
+pub struct RedisHealth;
+
+#[cfg(feature = "redis-rs")]
+#[async_trait]
+impl HealthCheck for RedisHealth {
+    fn name(&self) -> String {
+        "redis-rs".into()
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        let mut connection = crate::redis::try_get_redis_pool()
+            .ok_or(anyhow::anyhow!("redis未初始化"))?
+            .connection
+            .clone();
+        let _: String = ::redis::cmd("PING").query_async(&mut connection).await?;
+        Ok(())
+    }
+}
+
+/// 使用redisfred::init_redis_pool初始化的连接池
+#[cfg(feature = "redis-fred")]
+pub struct RedisFredHealth;
+
+#[cfg(feature = "redis-fred")]
+#[async_trait]
+impl HealthCheck for RedisFredHealth {
+    fn name(&self) -> String {
+        "redis-fred".into()
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        use fred::interfaces::ClientLike;
+        let redis =
+            crate::redisfred::try_get_redis_pool().ok_or(anyhow::anyhow!("redis未初始化"))?;
+        let _: String = redis.pool.ping().await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "kafka")]
+pub struct KafkaHealth {
+    pub client: crate::kafka::producer::KafkaClient,
+}
+
+#[cfg(feature = "kafka")]
+#[async_trait]
+impl HealthCheck for KafkaHealth {
+    fn name(&self) -> String {
+        "kafka".into()
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        use rdkafka::producer::Producer;
+        let producer = self.client.producer.clone();
+        // fetch_metadata是阻塞调用
+        tokio::task::spawn_blocking(move || {
+            producer
+                .client()
+                .fetch_metadata(None, Duration::from_secs(3))
+                .map(|_| ())
+        })
+        .await??;
+        Ok(())
+    }
+}
+
+/// 使用init_influxdb初始化的连接
+#[cfg(feature = "influx")]
+pub struct InfluxHealth;
+
+#[cfg(feature = "influx")]
+#[async_trait]
+impl HealthCheck for InfluxHealth {
+    fn name(&self) -> String {
+        "influxdb".into()
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        crate::influx::influxclient::get_influxdb().ping().await?;
+        Ok(())
+    }
+}
+
+/// IDM服务器，通过读取app的RBAC配置判断服务是否可用
+pub struct IdmHealth {
+    pub app_name: String,
+}
+
+#[async_trait]
+impl HealthCheck for IdmHealth {
+    fn name(&self) -> String {
+        "idm".into()
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        let idm = crate::idmlogin::try_get_idm().ok_or(anyhow::anyhow!("idm未初始化"))?;
+        idm.api
+            .check(&format!("/api/v1/janus/app/readrbac/{}", self.app_name))
+            .await?;
+        Ok(())
+    }
+}
+
+pub struct KeycenterHealth {
+    pub url: String,
+}
+
+#[async_trait]
+impl HealthCheck for KeycenterHealth {
+    fn name(&self) -> String {
+        "keycenter".into()
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        crate::keycenter::keycenter_check_health(&self.url).await
+    }
+}
+
+/// 单个依赖的检查结果
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct DependencyStatus {
+    pub name: String,
+    pub healthy: bool,
+    pub latency_ms: u64,
+    pub last_error: Option<String>, // 最近一次失败的原因，恢复后仍保留以便排查
+    pub checked_at: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReadyStatus {
+    pub ready: bool,
+    pub dependencies: Vec<DependencyStatus>,
+}
+
+/// 注册需要检查的依赖，/readyz时并发检查
+#[derive(Clone)]
+pub struct HealthRegistry {
+    pub checks: Vec<Arc<dyn HealthCheck>>,
+    pub timeout: Duration,
+    last_errors: Arc<DashMap<String, String>>,
+}
+
+impl Default for HealthRegistry {
+    fn default() -> Self {
+        Self {
+            checks: Vec::new(),
+            timeout: Duration::from_secs(3),
+            last_errors: Arc::new(DashMap::default()),
+        }
+    }
+}
+
+impl HealthRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_check<C: HealthCheck + 'static>(mut self, check: C) -> Self {
+        self.checks.push(Arc::new(check));
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn check_all(&self) -> ReadyStatus {
+        let dependencies =
+            futures::future::join_all(self.checks.iter().map(|check| self.check_one(check))).await;
+        ReadyStatus {
+            ready: dependencies.iter().all(|each| each.healthy),
+            dependencies,
+        }
+    }
+
+    async fn check_one(&self, check: &Arc<dyn HealthCheck>) -> DependencyStatus {
+        let name = check.name();
+        let start = Instant::now();
+        let result = match tokio::time::timeout(self.timeout, check.check()).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("timeout after {:?}", self.timeout)),
+        };
+        let latency_ms = start.elapsed().as_millis() as u64;
+        if let Err(e) = &result {
+            tracing::warn!("health check {} failed: {:?}", name, e);
+            self.last_errors.insert(name.clone(), format!("{}", e));
+        }
+        DependencyStatus {
+            healthy: result.is_ok(),
+            latency_ms,
+            last_error: self.last_errors.get(&name).map(|each| each.value().clone()),
+            checked_at: crate::time_util::get_current_time_east_8_rfc3339(),
+            name,
+        }
+    }
+}
+
+/// 挂载/healthz和/readyz
+/// - `App::new().service(health_scope("/health", registry))`
+pub fn health_scope(path: &str, registry: HealthRegistry) -> Scope {
+    web::scope(path)
+        .app_data(web::Data::new(registry))
+        .route("/healthz", web::get().to(healthz))
+        .route("/readyz", web::get().to(readyz))
+}
+
+/// 在根路径挂载/healthz和/readyz
+/// - `App::new().configure(health_config(registry))`
+pub fn health_config(registry: HealthRegistry) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg: &mut web::ServiceConfig| {
+        cfg.app_data(web::Data::new(registry))
+            .route("/healthz", web::get().to(healthz))
+            .route("/readyz", web::get().to(readyz));
+    }
+}
+
+/// 存活检查，只要进程能响应即可
+async fn healthz() -> HttpResponse {
+    Response::success(NoneBodyData {}).finished()
+}
+
+async fn readyz(registry: web::Data<HealthRegistry>) -> HttpResponse {
+    let status = registry.check_all().await;
+    if status.ready {
+        Response::success(status).finished()
+    } else {
+        Response::<NoneBodyData>::error(StatusCode::SERVICE_UNAVAILABLE, "依赖服务不可用")
+            .with_details(serde_json::json!(status))
+            .finished_with_status()
+    }
+}
+
+#[cfg(test)]
+mod health_tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    struct StubHealth {
+        healthy: bool,
+    }
+
+    #[async_trait]
+    impl HealthCheck for StubHealth {
+        fn name(&self) -> String {
+            format!("stub:{}", self.healthy)
+        }
+
+        async fn check(&self) -> anyhow::Result<()> {
+            match self.healthy {
+                true => Ok(()),
+                false => Err(anyhow::anyhow!("connection refused")),
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn readyz_test() {
+        let ready = HealthRegistry::new().with_check(StubHealth { healthy: true });
+        let failing = ready.clone().with_check(StubHealth { healthy: false });
+        let app = test::init_service(
+            App::new()
+                .service(health_scope("/ready", ready))
+                .service(health_scope("/failing", failing)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/ready/readyz").to_request();
+        let rsp = test::call_service(&app, req).await;
+        assert_eq!(rsp.status(), StatusCode::OK);
+
+        // 探针只看HTTP状态码，不受set_http_status_mode影响
+        let req = test::TestRequest::get().uri("/failing/readyz").to_request();
+        let rsp = test::call_service(&app, req).await;
+        assert_eq!(rsp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = test::read_body_json(rsp).await;
+        let dependencies = body["details"]["dependencies"].as_array().unwrap();
+        assert_eq!(dependencies.len(), 2);
+        assert_eq!(dependencies[1]["healthy"], false);
+        assert_eq!(dependencies[1]["last_error"], "connection refused");
+
+        let req = test::TestRequest::get()
+            .uri("/failing/healthz")
+            .to_request();
+        let rsp = test::call_service(&app, req).await;
+        assert_eq!(rsp.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::db_password::{get_salt_hash_password, verify_salt_hash_password};
+use crate::idmlogin::{AppReadRbacRsp, LoginReq, LoginRsp};
+use crate::keycenter_server::KeycenterConfig;
+use crate::rbac::RpConfig;
+use crate::token::{ACCESS_TOKEN_TIME, REFRESH_TOKEN_TIME};
+use crate::{NoneBodyData, Response};
+use actix_web::{web, HttpResponse, Scope};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const LOGIN_FAILED_MESSAGE: &str = "用户名或密码错误";
+// 应用不存在和秘钥错误返回相同的信息，避免探测已注册的应用
+const APP_LOGIN_FAILED_MESSAGE: &str = "应用不存在或秘钥错误";
+
+/// 用户不存在时用于校验的哈希，与正常用户的校验耗时一致
+fn dummy_password_hash() -> &'static String {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| {
+        get_salt_hash_password(&"webbase-dummy-password".to_string()).unwrap_or_default()
+    })
+}
+
+/// 本地用户，password为db_password::get_salt_hash_password生成的bcrypt哈希
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IdmUser {
+    pub id: u64,
+    pub account: String,
+    pub name: String,
+    pub password: String,
+}
+
+/// 用户文件格式
+/// ```json
+/// {
+///     "apps": { "demo.app": "demo_secret" },
+///     "users": [{ "id": 1, "account": "admin", "name": "管理员", "password": "$2b$12$..." }]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct IdmUsersFile {
+    pub apps: HashMap<String, String>, // appname -> appsecret
+    pub users: Vec<IdmUser>,
+}
+
+/// 本地IDM服务的数据，rbac以RpConfig.name作为app名字
+#[derive(Clone, Debug)]
+pub struct IdmServerConfig {
+    pub apps: HashMap<String, String>,
+    pub users: HashMap<String, IdmUser>,
+    pub rbac: HashMap<String, RpConfig>,
+    pub token: KeycenterConfig,
+}
+
+impl IdmServerConfig {
+    pub fn new(users: IdmUsersFile, rbac: Vec<RpConfig>, token: KeycenterConfig) -> Self {
+        Self {
+            apps: users.apps,
+            users: users
+                .users
+                .into_iter()
+                .map(|user| (user.account.clone(), user))
+                .collect(),
+            rbac: rbac
+                .into_iter()
+                .map(|config| (config.name.clone(), config))
+                .collect(),
+            token,
+        }
+    }
+
+    /// 从JSON格式的用户文件和RBAC文件加载
+    pub fn load<P: AsRef<Path>>(
+        users_file: P,
+        rbac_files: &[P],
+        token: KeycenterConfig,
+    ) -> anyhow::Result<Self> {
+        let users = serde_json::from_str::<IdmUsersFile>(&std::fs::read_to_string(users_file)?)?;
+        let mut rbac = Vec::<RpConfig>::default();
+        for each in rbac_files.iter() {
+            rbac.push(serde_json::from_str::<RpConfig>(&std::fs::read_to_string(
+                each,
+            )?)?);
+        }
+        Ok(Self::new(users, rbac, token))
+    }
+
+    pub fn login(&self, req: &LoginReq) -> anyhow::Result<LoginRsp> {
+        match self.apps.get(&req.appname) {
+            Some(secret) if secret.eq(&req.appsecret) => {}
+            Some(_) => {
+                tracing::warn!("idm login with wrong secret of app {}", req.appname);
+                return Err(anyhow::anyhow!(APP_LOGIN_FAILED_MESSAGE));
+            }
+            None => {
+                tracing::warn!("idm login with unknown app {}", req.appname);
+                return Err(anyhow::anyhow!(APP_LOGIN_FAILED_MESSAGE));
+            }
+        }
+        // 用户不存在时也做一次bcrypt校验，避免通过响应时间判断账号是否存在
+        let user = self.users.get(&req.account);
+        let hash = match user {
+            Some(user) => &user.password,
+            None => dummy_password_hash(),
+        };
+        let valid = verify_salt_hash_password(&req.password, hash).unwrap_or_else(|e| {
+            tracing::error!("verify password of {} error: {:?}", req.account, e);
+            false
+        });
+        let user = match user {
+            Some(user) if valid => user,
+            _ => return Err(anyhow::anyhow!(LOGIN_FAILED_MESSAGE)),
+        };
+
+        let access_token = self.token.encode_user_token(
+            user.id,
+            &user.account,
+            &user.name,
+            &req.appname,
+            ACCESS_TOKEN_TIME,
+        )?;
+        let refresh_token = self.token.encode_user_token(
+            user.id,
+            &user.account,
+            &user.name,
+            &req.appname,
+            REFRESH_TOKEN_TIME,
+        )?;
+        Ok(LoginRsp {
+            access_token,
+            refresh_token,
+            user_name: user.name.clone(),
+            user_account: user.account.clone(),
+            rbac: self.rbac.get(&req.appname).cloned(),
+        })
+    }
+}
+
+/// 挂载/api/v1/janus/app下IdmManager使用的接口
+/// - `App::new().service(idm_scope(config))`
+pub fn idm_scope(config: IdmServerConfig) -> Scope {
+    web::scope("/api/v1/janus/app")
+        .app_data(web::Data::new(config))
+        .route("/readrbac/{app}", web::get().to(read_rbac))
+        .route("/applogin", web::post().to(app_login))
+}
+
+async fn read_rbac(config: web::Data<IdmServerConfig>, app: web::Path<String>) -> HttpResponse {
+    let app = app.into_inner();
+    if !config.apps.contains_key(&app) && !config.rbac.contains_key(&app) {
+        return Response::<NoneBodyData>::nofound(&format!("应用不存在-{}", app)).finished();
+    }
+    Response::success(AppReadRbacRsp {
+        rbac: config.rbac.get(&app).cloned(),
+    })
+    .finished()
+}
+
+async fn app_login(config: web::Data<IdmServerConfig>, data: web::Json<LoginReq>) -> HttpResponse {
+    match config.login(&data) {
+        Ok(rsp) => Response::success(rsp).finished(),
+        Err(e) => {
+            tracing::warn!("idm login failed: {:?}", e);
+            Response::<NoneBodyData>::unauthorized(&format!("{}", e)).finished()
+        }
+    }
+}
+
+#[cfg(test)]
+mod idm_server_tests {
+    use super::*;
+
+    #[test]
+    fn login_failed_test() {
+        let users = IdmUsersFile {
+            apps: HashMap::from([("demo.app".to_string(), "demo_secret".to_string())]),
+            users: vec![IdmUser {
+                id: 1,
+                account: "admin".to_string(),
+                name: "管理员".to_string(),
+                password: "not-a-bcrypt-hash".to_string(),
+            }],
+        };
+        let config = IdmServerConfig::new(
+            users,
+            vec![],
+            KeycenterConfig::new_hs512("secret".to_string()),
+        );
+        for account in ["admin", "nobody"] {
+            let req = LoginReq {
+                appname: "demo.app".to_string(),
+                appsecret: "demo_secret".to_string(),
+                account: account.to_string(),
+                password: "password".to_string(),
+            };
+            let err = config.login(&req).unwrap_err();
+            assert_eq!(err.to_string(), LOGIN_FAILED_MESSAGE);
+        }
+        for (appname, appsecret) in [("demo.app", "wrong"), ("other.app", "demo_secret")] {
+            let req = LoginReq {
+                appname: appname.to_string(),
+                appsecret: appsecret.to_string(),
+                account: "admin".to_string(),
+                password: "password".to_string(),
+            };
+            let err = config.login(&req).unwrap_err();
+            assert_eq!(err.to_string(), APP_LOGIN_FAILED_MESSAGE);
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{rbac::RpConfig, ClientRsp};
+use crate::apiclient::{ApiClient, ApiError};
+use crate::rbac::RpConfig;
 use anyhow::anyhow;
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
@@ -29,7 +30,9 @@ pub struct LoginRsp {
 #[derive(Clone, Debug)]
 pub struct IdmManager {
     pub server: String,
+    #[deprecated(note = "使用api调用IDM服务，client与api共享连接池")]
     pub client: reqwest::Client,
+    pub api: ApiClient,
     pub rpinfo: Arc<DashMap<String, RpConfig>>,
 }
 
@@ -43,56 +46,38 @@ pub fn get_idm() -> &'static IdmManager {
     IDM.get().unwrap()
 }
 
+pub fn try_get_idm() -> Option<&'static IdmManager> {
+    IDM.get()
+}
+
 impl IdmManager {
     pub fn new(server: String, app_name: String) -> Self {
-        let client = reqwest::Client::builder()
-            .http1_only()
-            .connect_timeout(std::time::Duration::from_secs(5))
-            .timeout(std::time::Duration::from_secs(5))
-            // .tcp_keepalive(std::time::Duration::from_secs(120))
-            .build()
-            .unwrap();
+        let api = ApiClient::new(&server);
+        #[allow(deprecated)]
         let manager = Self {
-            server: server.clone(),
-            client: client.clone(),
+            server: api.base_url.clone(),
+            client: api.client.clone(),
+            api: api.clone(),
             rpinfo: Arc::new(DashMap::default()),
         };
 
-        let url = format!("{}/api/v1/janus/app/readrbac/{}", server, app_name);
+        let path = format!("/api/v1/janus/app/readrbac/{}", app_name);
         let rbac = futures::executor::block_on(async {
-            let response = client
-                .get(url)
-                .send()
-                .await
-                .map_err(|e| {
-                    tracing::error!("IDM服务器连接出错");
-                    e
-                })
-                .unwrap();
-            let response = response
-                .json::<ClientRsp>()
-                .await
-                .map_err(|e| {
-                    tracing::error!("IDM服务器返回的数据格式有误");
-                    e
-                })
-                .unwrap();
-            if response.status {
-                let resp_data = serde_json::from_value::<AppReadRbacRsp>(response.message.clone())
-                    .map_err(|e| {
-                        tracing::error!("IDM服务器返回的RBAC数据格式有误");
-                        e
-                    })
-                    .unwrap();
-                resp_data.rbac
-            } else {
-                panic!("获取IDM服务RBAC信息失败-{}", app_name);
+            match api.get::<AppReadRbacRsp>(&path).await {
+                Ok(resp_data) => resp_data.rbac,
+                Err(ApiError::Remote { message, .. }) => {
+                    panic!("获取IDM服务RBAC信息失败-{}: {}", app_name, message);
+                }
+                Err(e) => {
+                    tracing::error!("IDM服务器连接出错或返回的RBAC数据格式有误");
+                    panic!("获取IDM服务RBAC信息失败-{}: {}", app_name, e);
+                }
             }
         });
 
-        if rbac.is_some() {
+        if let Some(rbac) = rbac {
             tracing::warn!("use remote rbac config file");
-            manager.rpinfo.insert(app_name, rbac.unwrap());
+            manager.rpinfo.insert(app_name, rbac);
         }
         manager
     }
@@ -110,34 +95,15 @@ impl IdmManager {
             account: user_name.clone(),
             password: user_pass.clone(),
         };
-        let url = format!("{}/api/v1/janus/app/applogin", self.server);
-        let response = self.client.post(url).json(&req_data).send().await?;
-
-        if response.status().is_success() {
-            match response.json::<ClientRsp>().await {
-                Ok(resp) => {
-                    let status = resp.status;
-                    if status {
-                        let resp_data =
-                            serde_json::from_value::<LoginRsp>(resp.message.clone()).unwrap();
-                        if resp_data.rbac.is_some() {
-                            tracing::warn!("update remote rbac config file");
-                            self.rpinfo
-                                .insert(app_name, resp_data.rbac.clone().unwrap());
-                        }
-                        Ok(resp_data)
-                    } else {
-                        let reason =
-                            serde_json::from_value::<String>(resp.message.clone()).unwrap();
-                        return Err(anyhow!("login error reason: {}", reason));
-                    }
-                }
-                Err(e) => {
-                    return Err(anyhow!("login error as response data decoding: {:?}", e));
-                }
-            }
-        } else {
-            return Err(anyhow!("login error: {:?}", response.status()));
+        let resp_data = self
+            .api
+            .post::<LoginReq, LoginRsp>("/api/v1/janus/app/applogin", &req_data)
+            .await
+            .map_err(|e| anyhow!("login {}", e))?;
+        if let Some(rbac) = resp_data.rbac.clone() {
+            tracing::warn!("update remote rbac config file");
+            self.rpinfo.insert(app_name, rbac);
         }
+        Ok(resp_data)
     }
 }
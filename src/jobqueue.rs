@@ -0,0 +1,592 @@
+use crate::redisfred::{
+    connect_blocking_client, ensure_stream_group, lua_now_millis, quit_in_background, CachedScript,
+    StreamEntry,
+};
+use crate::time_util::get_now_millis;
+use fred::clients::{RedisClient, RedisPool};
+use fred::interfaces::StreamsInterface;
+use futures::FutureExt;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+static ENQUEUE_DELAYED_SCRIPT: CachedScript = CachedScript::new(concat!(
+    lua_now_millis!(),
+    r#"
+redis.call('ZADD', KEYS[1], now + tonumber(ARGV[1]), ARGV[2])
+return 1
+"#
+));
+
+// 把到期的延时任务移动到stream中
+static PROMOTE_SCRIPT: CachedScript = CachedScript::new(concat!(
+    lua_now_millis!(),
+    r#"
+local jobs = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', now, 'LIMIT', 0, tonumber(ARGV[1]))
+for _, job in ipairs(jobs) do
+    redis.call('XADD', KEYS[2], '*', 'job', job)
+    redis.call('ZREM', KEYS[1], job)
+end
+return #jobs
+"#
+));
+
+static ACK_SCRIPT: CachedScript = CachedScript::new(
+    r#"
+local acked = redis.call('XACK', KEYS[1], ARGV[1], ARGV[2])
+redis.call('XDEL', KEYS[1], ARGV[2])
+return acked
+"#,
+);
+
+// 确认失败的消息后按退避时间放回延时队列，消息已被确认时(超时后被其他worker处理)不再重试
+static RETRY_SCRIPT: CachedScript = CachedScript::new(concat!(
+    lua_now_millis!(),
+    r#"
+if redis.call('XACK', KEYS[1], ARGV[1], ARGV[2]) == 0 then
+    return 0
+end
+redis.call('XDEL', KEYS[1], ARGV[2])
+redis.call('ZADD', KEYS[2], now + tonumber(ARGV[3]), ARGV[4])
+return 1
+"#
+));
+
+static DEAD_SCRIPT: CachedScript = CachedScript::new(
+    r#"
+if redis.call('XACK', KEYS[1], ARGV[1], ARGV[2]) == 0 then
+    return 0
+end
+redis.call('XDEL', KEYS[1], ARGV[2])
+redis.call('XADD', KEYS[2], '*', 'job', ARGV[3], 'error', ARGV[4])
+return 1
+"#,
+);
+
+/// 队列中的任务，attempts为已失败的次数
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Job<T> {
+    pub id: String,
+    pub attempts: u32,
+    pub enqueued_at: u64, // 毫秒时间戳
+    pub last_error: Option<String>,
+    pub payload: T,
+}
+
+/// 基于redis stream和消费组的任务队列，使用redisfred的连接池
+/// - `{prefix:name}:stream`: 待处理的任务，由消费组内的worker竞争消费
+/// - `{prefix:name}:delayed`: 延时任务和等待重试的任务，按执行时间排序
+/// - `{prefix:name}:dead`: 超过重试次数的任务及最后一次的错误信息
+///
+/// 任务至少执行一次，handler需要保证幂等。处理时间超过visibility_timeout的任务会被认领并计为一次失败，
+/// 即使原来的处理最终成功也会消耗一次重试次数，并可能被再次执行，
+/// visibility_timeout需要大于任务最长的处理时间
+pub struct JobQueue<T> {
+    pub name: String,
+    pub prefix: String,
+    pub group: String,
+    pub max_retries: u32,
+    pub backoff: Duration,     // 第一次重试的等待时间，之后每次翻倍
+    pub max_backoff: Duration, // 重试等待时间的上限
+    pub visibility_timeout: Duration,
+    redis: RedisPool,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for JobQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            prefix: self.prefix.clone(),
+            group: self.group.clone(),
+            max_retries: self.max_retries,
+            backoff: self.backoff,
+            max_backoff: self.max_backoff,
+            visibility_timeout: self.visibility_timeout,
+            redis: self.redis.clone(),
+            _payload: PhantomData,
+        }
+    }
+}
+
+impl<T> JobQueue<T>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    pub fn new(redis: RedisPool, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            prefix: "jobs".into(),
+            group: "workers".into(),
+            max_retries: 5,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(3600),
+            visibility_timeout: Duration::from_secs(300),
+            redis,
+            _payload: PhantomData,
+        }
+    }
+
+    /// 使用redisfred::init_redis_pool初始化的连接池
+    pub fn fred(name: &str) -> Self {
+        Self::new(crate::redisfred::get_redis_pool().pool.clone(), name)
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_group(mut self, group: &str) -> Self {
+        self.group = group.to_string();
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_backoff(mut self, backoff: Duration, max_backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// 任务处理超过该时间未确认时视为worker已失效，由其他worker认领并计为一次失败
+    pub fn with_visibility_timeout(mut self, visibility_timeout: Duration) -> Self {
+        self.visibility_timeout = visibility_timeout;
+        self
+    }
+
+    // 使用相同的hash tag，集群部署时脚本操作的key在同一个slot
+    pub fn stream_key(&self) -> String {
+        format!("{{{}:{}}}:stream", self.prefix, self.name)
+    }
+
+    pub fn delayed_key(&self) -> String {
+        format!("{{{}:{}}}:delayed", self.prefix, self.name)
+    }
+
+    pub fn dead_key(&self) -> String {
+        format!("{{{}:{}}}:dead", self.prefix, self.name)
+    }
+
+    fn new_job(payload: T) -> anyhow::Result<(String, String)> {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let job = Job {
+            id: id.clone(),
+            attempts: 0,
+            enqueued_at: get_now_millis(),
+            last_error: None,
+            payload,
+        };
+        Ok((id, serde_json::to_string(&job)?))
+    }
+
+    /// 添加立即执行的任务，返回任务id
+    pub async fn enqueue(&self, payload: T) -> anyhow::Result<String> {
+        let (id, data) = Self::new_job(payload)?;
+        let _: String = self
+            .redis
+            .xadd(self.stream_key(), false, None, "*", vec![("job", data)])
+            .await?;
+        Ok(id)
+    }
+
+    /// 添加延时执行的任务，返回任务id
+    pub async fn enqueue_in(&self, payload: T, delay: Duration) -> anyhow::Result<String> {
+        let (id, data) = Self::new_job(payload)?;
+        let _: i64 = ENQUEUE_DELAYED_SCRIPT
+            .eval(
+                &self.redis,
+                self.delayed_key(),
+                vec![delay.as_millis().to_string(), data],
+            )
+            .await?;
+        Ok(id)
+    }
+
+    /// 添加在指定时间执行的任务，返回任务id
+    pub async fn enqueue_at(&self, payload: T, at: SystemTime) -> anyhow::Result<String> {
+        let delay = at.duration_since(SystemTime::now()).unwrap_or_default();
+        self.enqueue_in(payload, delay).await
+    }
+
+    /// 待处理(含处理中)的任务数
+    pub async fn len(&self) -> anyhow::Result<u64> {
+        Ok(self.redis.xlen(self.stream_key()).await?)
+    }
+
+    pub async fn is_empty(&self) -> anyhow::Result<bool> {
+        Ok(self.len().await? == 0)
+    }
+
+    pub async fn delayed_len(&self) -> anyhow::Result<u64> {
+        use fred::interfaces::SortedSetsInterface;
+        Ok(self.redis.zcard(self.delayed_key()).await?)
+    }
+
+    pub async fn dead_len(&self) -> anyhow::Result<u64> {
+        Ok(self.redis.xlen(self.dead_key()).await?)
+    }
+
+    async fn promote_delayed(&self, limit: usize) -> anyhow::Result<u64> {
+        Ok(PROMOTE_SCRIPT
+            .eval(
+                &self.redis,
+                vec![self.delayed_key(), self.stream_key()],
+                vec![limit.to_string()],
+            )
+            .await?)
+    }
+
+    async fn ack(&self, message_id: &str) -> anyhow::Result<()> {
+        let _: i64 = ACK_SCRIPT
+            .eval(
+                &self.redis,
+                self.stream_key(),
+                vec![self.group.clone(), message_id.to_string()],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 记录一次失败，未超过重试次数时放回延时队列，否则移入死信stream
+    async fn fail(&self, message_id: &str, data: &str, error: String) -> anyhow::Result<()> {
+        let mut job: Job<serde_json::Value> = match serde_json::from_str(data) {
+            Ok(job) => job,
+            Err(e) => return self.dead(message_id, data, format!("{:?}", e)).await,
+        };
+        job.attempts += 1;
+        job.last_error = Some(error.clone());
+        let data = serde_json::to_string(&job)?;
+        if job.attempts > self.max_retries {
+            tracing::warn!(
+                "job {}:{} failed after {} attempts: {}",
+                self.name,
+                job.id,
+                job.attempts,
+                error
+            );
+            return self.dead(message_id, &data, error).await;
+        }
+        let _: i64 = RETRY_SCRIPT
+            .eval(
+                &self.redis,
+                vec![self.stream_key(), self.delayed_key()],
+                vec![
+                    self.group.clone(),
+                    message_id.to_string(),
+                    self.backoff_delay(job.attempts).as_millis().to_string(),
+                    data,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn dead(&self, message_id: &str, data: &str, error: String) -> anyhow::Result<()> {
+        let _: i64 = DEAD_SCRIPT
+            .eval(
+                &self.redis,
+                vec![self.stream_key(), self.dead_key()],
+                vec![
+                    self.group.clone(),
+                    message_id.to_string(),
+                    data.to_string(),
+                    error,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 第attempts次失败后的等待时间
+    pub fn backoff_delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+/// 在tokio上运行的worker，同一个队列可以在多个进程中启动多个worker
+pub struct Worker<T> {
+    pub queue: JobQueue<T>,
+    pub consumer: String,
+    pub concurrency: usize,
+    pub block_timeout: Duration, // 每次阻塞读取的最长时间，也是响应停止信号的最长延迟
+}
+
+impl<T> Worker<T>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    pub fn new(queue: JobQueue<T>) -> Self {
+        Self {
+            queue,
+            consumer: format!(
+                "{}-{}",
+                whoami::fallible::hostname().unwrap_or_default(),
+                uuid::Uuid::new_v4().simple()
+            ),
+            concurrency: 4,
+            block_timeout: Duration::from_secs(2),
+        }
+    }
+
+    /// 消费者名字，默认为主机名加随机串
+    pub fn with_consumer(mut self, consumer: &str) -> Self {
+        self.consumer = consumer.to_string();
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_block_timeout(mut self, block_timeout: Duration) -> Self {
+        self.block_timeout = block_timeout;
+        self
+    }
+
+    /// 持续处理任务直到shutdown完成，之后不再读取新任务并等待处理中的任务结束
+    /// ```ignore
+    /// let queue = JobQueue::<SendMail>::fred("mail");
+    /// Worker::new(queue)
+    ///     .run(|job| async move { send_mail(job.payload).await }, async {
+    ///         let _ = tokio::signal::ctrl_c().await;
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn run<F, Fut, S>(self, handler: F, shutdown: S) -> anyhow::Result<()>
+    where
+        F: Fn(Job<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+        S: Future<Output = ()>,
+    {
+        let queue = &self.queue;
+        ensure_stream_group(&queue.redis, &queue.stream_key(), &queue.group).await?;
+        let reader = connect_blocking_client(&queue.redis).await?;
+
+        let handler = Arc::new(handler);
+        let semaphore = Arc::new(Semaphore::new(self.concurrency));
+        tokio::pin!(shutdown);
+        loop {
+            let permit = tokio::select! {
+                _ = &mut shutdown => break,
+                permit = semaphore.clone().acquire_owned() => permit?,
+            };
+            let mut permits = vec![permit];
+            while let Ok(permit) = semaphore.clone().try_acquire_owned() {
+                permits.push(permit);
+            }
+            if let Err(e) = self.poll(&reader, &handler, permits).await {
+                tracing::error!("job queue {} poll error: {:?}", self.queue.name, e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            if (&mut shutdown).now_or_never().is_some() {
+                break;
+            }
+        }
+
+        tracing::info!("job queue {} worker stopping", self.queue.name);
+        let _ = semaphore.acquire_many(self.concurrency as u32).await?;
+        quit_in_background(&reader);
+        Ok(())
+    }
+
+    async fn poll<F, Fut>(
+        &self,
+        reader: &RedisClient,
+        handler: &Arc<F>,
+        mut permits: Vec<OwnedSemaphorePermit>,
+    ) -> anyhow::Result<()>
+    where
+        F: Fn(Job<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let queue = &self.queue;
+        queue.promote_delayed(100).await?;
+
+        // 超过visibility_timeout未确认的任务视为失败
+        let (_, claimed): (String, Vec<StreamEntry>) = queue
+            .redis
+            .xautoclaim_values(
+                queue.stream_key(),
+                queue.group.as_str(),
+                self.consumer.as_str(),
+                queue.visibility_timeout.as_millis() as u64,
+                "0-0",
+                Some(100),
+                false,
+            )
+            .await?;
+        for (message_id, fields) in claimed {
+            let data = fields.get("job").cloned().unwrap_or_default();
+            queue
+                .fail(&message_id, &data, "visibility timeout".into())
+                .await?;
+        }
+
+        let messages: HashMap<String, Vec<StreamEntry>> = reader
+            .xreadgroup_map(
+                queue.group.as_str(),
+                self.consumer.as_str(),
+                Some(permits.len() as u64),
+                Some(self.block_timeout.as_millis() as u64),
+                false,
+                queue.stream_key(),
+                ">",
+            )
+            .await?;
+        for (message_id, fields) in messages.into_values().flatten() {
+            let Some(permit) = permits.pop() else {
+                break;
+            };
+            let data = fields.get("job").cloned().unwrap_or_default();
+            let queue = queue.clone();
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let _permit = permit;
+                let res = match serde_json::from_str::<Job<T>>(&data) {
+                    Ok(job) => handler(job).await,
+                    Err(e) => Err(e.into()),
+                };
+                let res = match res {
+                    Ok(()) => queue.ack(&message_id).await,
+                    Err(e) => queue.fail(&message_id, &data, format!("{:?}", e)).await,
+                };
+                if let Err(e) = res {
+                    tracing::error!(
+                        "job queue {} update {} error: {:?}",
+                        queue.name,
+                        message_id,
+                        e
+                    );
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod jobqueue_tests {
+    use super::*;
+
+    #[test]
+    fn backoff_test() {
+        let queue = JobQueue::<String>::new(
+            fred::clients::RedisPool::new(Default::default(), None, None, None, 1).unwrap(),
+            "test",
+        )
+        .with_backoff(Duration::from_secs(1), Duration::from_secs(10));
+        assert_eq!(queue.backoff_delay(1), Duration::from_secs(1));
+        assert_eq!(queue.backoff_delay(3), Duration::from_secs(4));
+        assert_eq!(queue.backoff_delay(10), Duration::from_secs(10));
+        assert_eq!(queue.backoff_delay(100), Duration::from_secs(10));
+        assert_eq!(queue.stream_key(), "{jobs:test}:stream");
+    }
+
+    #[tokio::test]
+    #[ignore = "需要redis，设置REDIS_URL后使用--ignored运行"]
+    async fn queue_test() {
+        use fred::interfaces::{ClientLike, KeysInterface};
+        use std::sync::Mutex;
+
+        let url = std::env::var("REDIS_URL").unwrap();
+        let redis = RedisPool::new(
+            fred::types::RedisConfig::from_url(&url).unwrap(),
+            None,
+            None,
+            None,
+            1,
+        )
+        .unwrap();
+        redis.init().await.unwrap();
+        let queue = JobQueue::<u64>::new(redis.clone(), &uuid::Uuid::new_v4().simple().to_string())
+            .with_prefix("jobqueue_test")
+            .with_max_retries(1)
+            .with_backoff(Duration::ZERO, Duration::ZERO)
+            .with_visibility_timeout(Duration::from_millis(200));
+
+        queue.enqueue(1).await.unwrap();
+        queue.enqueue_in(2, Duration::ZERO).await.unwrap();
+        assert_eq!(queue.len().await.unwrap(), 1);
+        assert_eq!(queue.delayed_len().await.unwrap(), 1);
+        assert_eq!(queue.promote_delayed(10).await.unwrap(), 1);
+        assert_eq!(queue.len().await.unwrap(), 2);
+        assert_eq!(queue.delayed_len().await.unwrap(), 0);
+
+        // 消费组已存在时忽略BUSYGROUP，未确认的任务超时后被认领并计为一次失败
+        let stream = queue.stream_key();
+        ensure_stream_group(&redis, &stream, &queue.group)
+            .await
+            .unwrap();
+        queue.enqueue(3).await.unwrap();
+        let stuck: HashMap<String, Vec<StreamEntry>> = redis
+            .xreadgroup_map(
+                queue.group.as_str(),
+                "stuck",
+                Some(1),
+                None,
+                false,
+                &stream,
+                ">",
+            )
+            .await
+            .unwrap();
+        assert_eq!(stuck.values().flatten().count(), 1);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        // 1成功，2一直失败，重试一次后进入死信，3被认领后重试成功
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let handler = {
+            let calls = calls.clone();
+            move |job: Job<u64>| {
+                calls.lock().unwrap().push((job.payload, job.attempts));
+                async move {
+                    match job.payload {
+                        2 => Err(anyhow::anyhow!("failed")),
+                        _ => Ok(()),
+                    }
+                }
+            }
+        };
+        let done = {
+            let queue = queue.clone();
+            async move {
+                loop {
+                    let finished = queue.dead_len().await.unwrap() == 1
+                        && queue.is_empty().await.unwrap()
+                        && queue.delayed_len().await.unwrap() == 0;
+                    if finished {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+            }
+        };
+        let worker = Worker::new(queue.clone()).with_block_timeout(Duration::from_millis(100));
+        tokio::time::timeout(Duration::from_secs(10), worker.run(handler, done))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut calls = calls.lock().unwrap().clone();
+        calls.sort();
+        assert_eq!(calls, vec![(1, 0), (2, 0), (2, 1), (3, 1)]);
+        let _: i64 = redis
+            .del(vec![stream, queue.delayed_key(), queue.dead_key()])
+            .await
+            .unwrap();
+    }
+}
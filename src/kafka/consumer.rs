@@ -12,7 +12,25 @@ use tokio::sync::mpsc::UnboundedSender;
 use tracing::{info, trace, warn};
 
 pub struct CustomContext;
-impl ClientContext for CustomContext {}
+impl ClientContext for CustomContext {
+    /// statistics.interval.ms开启后定期回调，用于记录各分区的消费延迟
+    #[cfg(feature = "metrics")]
+    fn stats(&self, statistics: rdkafka::statistics::Statistics) {
+        let metrics = crate::metrics::get_metrics();
+        for (topic_name, topic) in statistics.topics.iter() {
+            for (partition_id, partition) in topic.partitions.iter() {
+                // -1为rdkafka内部使用的UA分区，consumer_lag为-1表示未知
+                if *partition_id < 0 || partition.consumer_lag < 0 {
+                    continue;
+                }
+                metrics
+                    .kafka_consumer_lag
+                    .with_label_values(&[topic_name.as_str(), partition_id.to_string().as_str()])
+                    .set(partition.consumer_lag);
+            }
+        }
+    }
+}
 impl ConsumerContext for CustomContext {
     fn pre_rebalance(&self, rebalance: &Rebalance) {
         info!("Pre rebalance {:?}", rebalance);
@@ -53,6 +71,8 @@ impl KafkaConsumer {
         creator.set("session.timeout.ms", "6000");
         creator.set("enable.auto.commit", "false");
         // creator.set("statistics.interval.ms", "30000");
+        #[cfg(feature = "metrics")]
+        creator.set("statistics.interval.ms", "30000");
         // creator.set("auto.offset.reset", "smallest");
         // creator.set_log_level(RDKafkaLogLevel::Debug);
 
@@ -71,8 +91,20 @@ impl KafkaConsumer {
             let mut message_stream = consumer.stream();
             while let Some(message) = message_stream.next().await {
                 match message {
-                    Err(e) => warn!("Kafka strem error: {}", e),
+                    Err(e) => {
+                        #[cfg(feature = "metrics")]
+                        crate::metrics::get_metrics()
+                            .kafka_errors
+                            .with_label_values(&["consumer"])
+                            .inc();
+                        warn!("Kafka strem error: {}", e)
+                    }
                     Ok(m) => {
+                        #[cfg(feature = "metrics")]
+                        crate::metrics::get_metrics()
+                            .kafka_messages
+                            .with_label_values(&["consumed", m.topic()])
+                            .inc();
                         // let payload = m.payload_view::<str>().unwrap();
                         // let payload = match m.payload_view::<str>() {
                         //     None => "",
@@ -27,7 +27,7 @@ impl KafkaClient {
     }
 
     pub async fn send_text(&self, topic_name: &str, data: &str, key: String) -> anyhow::Result<()> {
-        let _delivery_status = self
+        let delivery_status = self
             .producer
             .send(
                 FutureRecord::to(topic_name)
@@ -39,11 +39,10 @@ impl KafkaClient {
                     })),
                 Duration::from_secs(0),
             )
-            .await
-            .map_err(|e| {
-                return Err::<(), anyhow::Error>(anyhow!("send kafka msg with error: {:?}", e));
-            })
-            .unwrap();
+            .await;
+        #[cfg(feature = "metrics")]
+        record_send(topic_name, &delivery_status);
+        delivery_status.map_err(|e| anyhow!("send kafka msg with error: {:?}", e))?;
         anyhow::Ok(())
     }
 
@@ -53,7 +52,7 @@ impl KafkaClient {
         data: &Vec<u8>,
         key: String,
     ) -> anyhow::Result<()> {
-        let _delivery_status = self
+        let delivery_status = self
             .producer
             .send(
                 FutureRecord::to(topic_name)
@@ -65,11 +64,23 @@ impl KafkaClient {
                     })),
                 Duration::from_secs(0),
             )
-            .await
-            .map_err(|e| {
-                return Err::<(), anyhow::Error>(anyhow!("send kafka msg with error: {:?}", e));
-            })
-            .unwrap();
+            .await;
+        #[cfg(feature = "metrics")]
+        record_send(topic_name, &delivery_status);
+        delivery_status.map_err(|e| anyhow!("send kafka msg with error: {:?}", e))?;
         anyhow::Ok(())
     }
 }
+
+#[cfg(feature = "metrics")]
+fn record_send<T, E>(topic_name: &str, delivery_status: &Result<T, E>) {
+    let metrics = crate::metrics::get_metrics();
+    if delivery_status.is_ok() {
+        metrics
+            .kafka_messages
+            .with_label_values(&["produced", topic_name])
+            .inc();
+    } else {
+        metrics.kafka_errors.with_label_values(&["producer"]).inc();
+    }
+}
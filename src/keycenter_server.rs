@@ -0,0 +1,190 @@
+use crate::keycenter::{JwtAccessRsp, JwtDecodeRsp, JwtEncodeReq, JwtEncodeRsp};
+use crate::token::{AccessToken, ACCESS_TOKEN_TIME, REFRESH_TOKEN_TIME};
+use crate::token_rsa::AccessTokenRsa;
+use crate::{NoneBodyData, Response};
+use actix_web::{web, HttpResponse, Scope};
+
+/// 本地keycenter服务的配置，use_rsa时encode_secret为RSA私钥，decode_secret为RSA公钥，
+/// 否则两者均为HS512的秘钥
+#[derive(Clone, Debug)]
+pub struct KeycenterConfig {
+    pub use_rsa: bool,
+    pub encode_secret: String,
+    pub decode_secret: String,
+}
+
+impl KeycenterConfig {
+    pub fn new_hs512(secret: String) -> Self {
+        Self {
+            use_rsa: false,
+            encode_secret: secret.clone(),
+            decode_secret: secret,
+        }
+    }
+
+    pub fn new_rsa(rsa_private: String, rsa_public: String) -> Self {
+        Self {
+            use_rsa: true,
+            encode_secret: rsa_private,
+            decode_secret: rsa_public,
+        }
+    }
+
+    pub fn encode_token(&self, data: &JwtEncodeReq, timeout_hour: u16) -> anyhow::Result<String> {
+        self.encode_user_token(0, &data.account, &data.name, &data.appid, timeout_hour)
+    }
+
+    pub fn encode_user_token(
+        &self,
+        user_id: u64,
+        user_account: &String,
+        user_name: &String,
+        app_id: &String,
+        timeout_hour: u16,
+    ) -> anyhow::Result<String> {
+        if self.use_rsa {
+            AccessTokenRsa::encode_token(
+                user_id,
+                user_account,
+                user_name,
+                app_id,
+                timeout_hour,
+                &self.encode_secret,
+            )
+        } else {
+            AccessToken::encode_token(
+                user_id,
+                user_account,
+                user_name,
+                app_id,
+                timeout_hour,
+                &self.encode_secret,
+            )
+        }
+    }
+
+    pub fn decode_token(&self, token: &String) -> anyhow::Result<AccessToken> {
+        if self.use_rsa {
+            Ok(AccessTokenRsa::decode_token(token, &self.decode_secret)?.to_token())
+        } else {
+            AccessToken::decode_token(token, &self.decode_secret)
+        }
+    }
+}
+
+const INVALID_TOKEN_MESSAGE: &str = "token无效或已过期";
+
+/// 挂载/api/v1/keycenter下与keycenter客户端函数对应的接口
+/// - `App::new().service(keycenter_scope(config))`
+pub fn keycenter_scope(config: KeycenterConfig) -> Scope {
+    web::scope("/api/v1/keycenter")
+        .app_data(web::Data::new(config))
+        .route("/health", web::get().to(health))
+        .route("/encode", web::post().to(encode))
+        .route("/access", web::post().to(access))
+        .route("/refresh", web::post().to(refresh))
+        .route("/decode", web::post().to(decode))
+}
+
+async fn health() -> HttpResponse {
+    Response::success(NoneBodyData {}).finished()
+}
+
+async fn encode(config: web::Data<KeycenterConfig>, data: web::Json<JwtEncodeReq>) -> HttpResponse {
+    let access = config.encode_token(&data, ACCESS_TOKEN_TIME);
+    let refresh = config.encode_token(&data, REFRESH_TOKEN_TIME);
+    match (access, refresh) {
+        (Ok(access), Ok(refresh)) => Response::success(JwtEncodeRsp { access, refresh }).finished(),
+        (Err(e), _) | (_, Err(e)) => Response::<NoneBodyData>::from(e).finished(),
+    }
+}
+
+async fn access(config: web::Data<KeycenterConfig>, data: web::Json<JwtEncodeReq>) -> HttpResponse {
+    match config.encode_token(&data, ACCESS_TOKEN_TIME) {
+        Ok(token) => Response::success(JwtAccessRsp { token }).finished(),
+        Err(e) => Response::<NoneBodyData>::from(e).finished(),
+    }
+}
+
+async fn refresh(
+    config: web::Data<KeycenterConfig>,
+    data: web::Json<JwtEncodeReq>,
+) -> HttpResponse {
+    match config.encode_token(&data, REFRESH_TOKEN_TIME) {
+        Ok(token) => Response::success(JwtAccessRsp { token }).finished(),
+        Err(e) => Response::<NoneBodyData>::from(e).finished(),
+    }
+}
+
+async fn decode(config: web::Data<KeycenterConfig>, data: web::Json<JwtAccessRsp>) -> HttpResponse {
+    match config.decode_token(&data.token) {
+        Ok(access) => Response::success(JwtDecodeRsp {
+            account: access.user_account,
+            name: access.user_name,
+            appid: access.app_id,
+            exp: access.exp,
+        })
+        .finished(),
+        Err(e) => {
+            tracing::warn!("decode token error: {:?}", e);
+            Response::<NoneBodyData>::bad_request(INVALID_TOKEN_MESSAGE).finished()
+        }
+    }
+}
+
+#[cfg(test)]
+mod keycenter_server_tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    #[actix_web::test]
+    async fn keycenter_scope_test() {
+        let app = test::init_service(App::new().service(keycenter_scope(
+            KeycenterConfig::new_hs512("secret".to_string()),
+        )))
+        .await;
+        let post = |path: &str, data: serde_json::Value| {
+            test::TestRequest::post()
+                .uri(&format!("/api/v1/keycenter{}", path))
+                .set_json(data)
+                .to_request()
+        };
+        let user = serde_json::json!({ "account": "zhangsan", "name": "张三", "appid": "app" });
+
+        let rsp: serde_json::Value =
+            test::call_and_read_body_json(&app, post("/encode", user.clone())).await;
+        let tokens: JwtEncodeRsp = serde_json::from_value(rsp["message"].clone()).unwrap();
+        for path in ["/access", "/refresh"] {
+            let rsp: serde_json::Value =
+                test::call_and_read_body_json(&app, post(path, user.clone())).await;
+            let token: JwtAccessRsp = serde_json::from_value(rsp["message"].clone()).unwrap();
+            let rsp: serde_json::Value = test::call_and_read_body_json(
+                &app,
+                post("/decode", serde_json::json!({ "token": token.token })),
+            )
+            .await;
+            assert_eq!(rsp["message"]["account"], "zhangsan");
+        }
+        let rsp: serde_json::Value = test::call_and_read_body_json(
+            &app,
+            post("/decode", serde_json::json!({ "token": tokens.refresh })),
+        )
+        .await;
+        let decoded: JwtDecodeRsp = serde_json::from_value(rsp["message"].clone()).unwrap();
+        assert_eq!(
+            (decoded.name.as_str(), decoded.appid.as_str()),
+            ("张三", "app")
+        );
+
+        let rsp: serde_json::Value = test::call_and_read_body_json(
+            &app,
+            post(
+                "/decode",
+                serde_json::json!({ "token": tokens.access + "x" }),
+            ),
+        )
+        .await;
+        assert_eq!(rsp["code"], 400);
+        assert_eq!(rsp["message"], INVALID_TOKEN_MESSAGE);
+    }
+}
@@ -0,0 +1,369 @@
+use super::{KvStore, KvTtl};
+use async_trait::async_trait;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+enum MemoryData {
+    Bytes(Vec<u8>),
+    Hash(HashMap<String, Vec<u8>>),
+    List(VecDeque<Vec<u8>>),
+    Set(HashSet<Vec<u8>>),
+}
+
+struct MemoryValue {
+    data: MemoryData,
+    expires_at: Option<Instant>,
+}
+
+impl MemoryValue {
+    fn new(data: MemoryData, ttl: Option<Duration>) -> Self {
+        Self {
+            data,
+            expires_at: ttl.map(|ttl| Instant::now() + ttl),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Instant::now())
+    }
+}
+
+fn wrong_type(key: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "WRONGTYPE Operation against a key holding the wrong kind of value: {}",
+        key
+    )
+}
+
+/// 进程内的KvStore，过期的key在访问时清除，用于测试或单实例部署
+#[derive(Default)]
+pub struct MemoryKvStore {
+    data: DashMap<String, MemoryValue>,
+}
+
+impl MemoryKvStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 读取未过期的值
+    fn read<T>(&self, key: &str, f: impl FnOnce(&MemoryData) -> T) -> Option<T> {
+        let value = self.data.get(key)?;
+        if value.is_expired() {
+            drop(value);
+            self.data.remove_if(key, |_, value| value.is_expired());
+            return None;
+        }
+        Some(f(&value.data))
+    }
+
+    /// 修改未过期的值，不存在时用init创建
+    fn write<T>(
+        &self,
+        key: &str,
+        init: impl FnOnce() -> MemoryData,
+        f: impl FnOnce(&mut MemoryData) -> T,
+    ) -> T {
+        let mut entry = match self.data.entry(key.to_string()) {
+            Entry::Occupied(mut entry) => {
+                if entry.get().is_expired() {
+                    entry.insert(MemoryValue::new(init(), None));
+                }
+                entry.into_ref()
+            }
+            Entry::Vacant(entry) => entry.insert(MemoryValue::new(init(), None)),
+        };
+        f(&mut entry.data)
+    }
+}
+
+#[async_trait]
+impl KvStore for MemoryKvStore {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.read(key, |data| match data {
+            MemoryData::Bytes(value) => Ok(value.clone()),
+            _ => Err(wrong_type(key)),
+        }) {
+            Some(value) => Ok(Some(value?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        self.data.insert(
+            key.to_string(),
+            MemoryValue::new(MemoryData::Bytes(value), ttl),
+        );
+        Ok(())
+    }
+
+    async fn set_nx(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<bool> {
+        match self.data.entry(key.to_string()) {
+            Entry::Occupied(mut entry) => {
+                if !entry.get().is_expired() {
+                    return Ok(false);
+                }
+                entry.insert(MemoryValue::new(MemoryData::Bytes(value), ttl));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(MemoryValue::new(MemoryData::Bytes(value), ttl));
+            }
+        }
+        Ok(true)
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<bool> {
+        Ok(self
+            .data
+            .remove(key)
+            .is_some_and(|(_, value)| !value.is_expired()))
+    }
+
+    async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        Ok(self.read(key, |_| ()).is_some())
+    }
+
+    async fn mget(&self, keys: &[String]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            // 与redis相同，类型不是字符串的key返回nil
+            values.push(self.get(key).await.ok().flatten());
+        }
+        Ok(values)
+    }
+
+    async fn mset(
+        &self,
+        items: Vec<(String, Vec<u8>)>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        for (key, value) in items {
+            self.data
+                .insert(key, MemoryValue::new(MemoryData::Bytes(value), ttl));
+        }
+        Ok(())
+    }
+
+    async fn ttl(&self, key: &str) -> anyhow::Result<KvTtl> {
+        Ok(match self.data.get(key) {
+            Some(value) if !value.is_expired() => match value.expires_at {
+                Some(expires_at) => {
+                    KvTtl::Expires(expires_at.saturating_duration_since(Instant::now()))
+                }
+                None => KvTtl::Persistent,
+            },
+            _ => KvTtl::Missing,
+        })
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> anyhow::Result<bool> {
+        Ok(match self.data.get_mut(key) {
+            Some(mut value) if !value.is_expired() => {
+                value.expires_at = Some(Instant::now() + ttl);
+                true
+            }
+            _ => false,
+        })
+    }
+
+    async fn hget(&self, key: &str, field: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.read(key, |data| match data {
+            MemoryData::Hash(hash) => Ok(hash.get(field).cloned()),
+            _ => Err(wrong_type(key)),
+        }) {
+            Some(value) => value,
+            None => Ok(None),
+        }
+    }
+
+    async fn hset(&self, key: &str, field: &str, value: Vec<u8>) -> anyhow::Result<()> {
+        self.write(
+            key,
+            || MemoryData::Hash(HashMap::new()),
+            |data| match data {
+                MemoryData::Hash(hash) => {
+                    hash.insert(field.to_string(), value);
+                    Ok(())
+                }
+                _ => Err(wrong_type(key)),
+            },
+        )
+    }
+
+    async fn hdel(&self, key: &str, field: &str) -> anyhow::Result<bool> {
+        match self.data.get_mut(key) {
+            Some(mut value) if !value.is_expired() => match &mut value.data {
+                MemoryData::Hash(hash) => Ok(hash.remove(field).is_some()),
+                _ => Err(wrong_type(key)),
+            },
+            _ => Ok(false),
+        }
+    }
+
+    async fn hgetall(&self, key: &str) -> anyhow::Result<HashMap<String, Vec<u8>>> {
+        match self.read(key, |data| match data {
+            MemoryData::Hash(hash) => Ok(hash.clone()),
+            _ => Err(wrong_type(key)),
+        }) {
+            Some(value) => value,
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    async fn rpush(&self, key: &str, value: Vec<u8>) -> anyhow::Result<u64> {
+        self.write(
+            key,
+            || MemoryData::List(VecDeque::new()),
+            |data| match data {
+                MemoryData::List(list) => {
+                    list.push_back(value);
+                    Ok(list.len() as u64)
+                }
+                _ => Err(wrong_type(key)),
+            },
+        )
+    }
+
+    async fn lpop(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.data.get_mut(key) {
+            Some(mut value) if !value.is_expired() => match &mut value.data {
+                MemoryData::List(list) => Ok(list.pop_front()),
+                _ => Err(wrong_type(key)),
+            },
+            _ => Ok(None),
+        }
+    }
+
+    async fn lrange(&self, key: &str, start: i64, stop: i64) -> anyhow::Result<Vec<Vec<u8>>> {
+        match self.read(key, |data| match data {
+            MemoryData::List(list) => {
+                // 与LRANGE相同，负数表示从末尾开始计数，stop包含在内
+                let len = list.len() as i64;
+                let start = if start < 0 { len + start } else { start }.max(0);
+                let stop = if stop < 0 { len + stop } else { stop }.min(len - 1);
+                if start > stop {
+                    return Ok(Vec::new());
+                }
+                Ok(list
+                    .iter()
+                    .skip(start as usize)
+                    .take((stop - start + 1) as usize)
+                    .cloned()
+                    .collect())
+            }
+            _ => Err(wrong_type(key)),
+        }) {
+            Some(value) => value,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn llen(&self, key: &str) -> anyhow::Result<u64> {
+        match self.read(key, |data| match data {
+            MemoryData::List(list) => Ok(list.len() as u64),
+            _ => Err(wrong_type(key)),
+        }) {
+            Some(value) => value,
+            None => Ok(0),
+        }
+    }
+
+    async fn sadd(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool> {
+        self.write(
+            key,
+            || MemoryData::Set(HashSet::new()),
+            |data| match data {
+                MemoryData::Set(set) => Ok(set.insert(member)),
+                _ => Err(wrong_type(key)),
+            },
+        )
+    }
+
+    async fn srem(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool> {
+        match self.data.get_mut(key) {
+            Some(mut value) if !value.is_expired() => match &mut value.data {
+                MemoryData::Set(set) => Ok(set.remove(&member)),
+                _ => Err(wrong_type(key)),
+            },
+            _ => Ok(false),
+        }
+    }
+
+    async fn smembers(&self, key: &str) -> anyhow::Result<Vec<Vec<u8>>> {
+        match self.read(key, |data| match data {
+            MemoryData::Set(set) => Ok(set.iter().cloned().collect()),
+            _ => Err(wrong_type(key)),
+        }) {
+            Some(value) => value,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn sismember(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool> {
+        match self.read(key, |data| match data {
+            MemoryData::Set(set) => Ok(set.contains(&member)),
+            _ => Err(wrong_type(key)),
+        }) {
+            Some(value) => value,
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use super::*;
+    use crate::cache::Cache;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn memory_kv_store_test() {
+        let cache = Cache::new(Arc::new(MemoryKvStore::new()), "test");
+        cache
+            .set("a", &vec![1, 2, 3], Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        assert_eq!(
+            cache.get::<Vec<i32>>("a").await.unwrap(),
+            Some(vec![1, 2, 3])
+        );
+        assert!(matches!(cache.ttl("a").await.unwrap(), KvTtl::Expires(_)));
+        assert_eq!(cache.ttl("b").await.unwrap(), KvTtl::Missing);
+        assert!(!cache.set_nx("a", &0, None).await.unwrap());
+
+        cache
+            .set("c", &1, Some(Duration::from_millis(1)))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(cache.get::<i32>("c").await.unwrap(), None);
+
+        cache.hset("h", "f", &"v").await.unwrap();
+        assert_eq!(
+            cache.hget::<String>("h", "f").await.unwrap().as_deref(),
+            Some("v")
+        );
+        assert!(cache.get::<String>("h").await.is_err());
+
+        for i in 0..5 {
+            cache.rpush("l", &i).await.unwrap();
+        }
+        assert_eq!(
+            cache.lrange::<i32>("l", 1, -2).await.unwrap(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(cache.lpop::<i32>("l").await.unwrap(), Some(0));
+
+        assert!(cache.sadd("s", &"x").await.unwrap());
+        assert!(!cache.sadd("s", &"x").await.unwrap());
+        assert!(cache.sismember("s", &"x").await.unwrap());
+    }
+}
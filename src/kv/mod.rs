@@ -0,0 +1,129 @@
+#[cfg(feature = "redis-fred")]
+mod redis_fred;
+#[cfg(feature = "redis-rs")]
+mod redis_rs;
+
+pub mod memory;
+pub use memory::MemoryKvStore;
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// key的剩余有效期
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KvTtl {
+    Missing,    // key不存在
+    Persistent, // 没有设置过期时间
+    Expires(Duration),
+}
+
+impl KvTtl {
+    /// 由PTTL的返回值转换
+    pub fn from_pttl(pttl: i64) -> Self {
+        match pttl {
+            -2 => KvTtl::Missing,
+            -1 => KvTtl::Persistent,
+            ms => KvTtl::Expires(Duration::from_millis(ms.max(0) as u64)),
+        }
+    }
+}
+
+/// 键值存储，值均为序列化后的字节，应用代码通过该trait使用redis而不依赖具体的redis库
+/// - redis-rs: 启用`redis-rs` feature，为redis::RedisPool实现
+/// - fred: 启用`redis-fred` feature，为redisfred::RedisCachePool实现
+/// - MemoryKvStore: 进程内实现，用于测试或未部署redis时
+#[async_trait]
+pub trait KvStore: Send + Sync {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()>;
+    /// key不存在时才写入，返回是否写入成功
+    async fn set_nx(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<bool>;
+    async fn delete(&self, key: &str) -> anyhow::Result<bool>;
+    async fn exists(&self, key: &str) -> anyhow::Result<bool>;
+    async fn mget(&self, keys: &[String]) -> anyhow::Result<Vec<Option<Vec<u8>>>>;
+    async fn mset(
+        &self,
+        items: Vec<(String, Vec<u8>)>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<()>;
+    async fn ttl(&self, key: &str) -> anyhow::Result<KvTtl>;
+    async fn expire(&self, key: &str, ttl: Duration) -> anyhow::Result<bool>;
+
+    async fn hget(&self, key: &str, field: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    async fn hset(&self, key: &str, field: &str, value: Vec<u8>) -> anyhow::Result<()>;
+    async fn hdel(&self, key: &str, field: &str) -> anyhow::Result<bool>;
+    async fn hgetall(&self, key: &str) -> anyhow::Result<HashMap<String, Vec<u8>>>;
+
+    async fn rpush(&self, key: &str, value: Vec<u8>) -> anyhow::Result<u64>;
+    async fn lpop(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    async fn lrange(&self, key: &str, start: i64, stop: i64) -> anyhow::Result<Vec<Vec<u8>>>;
+    async fn llen(&self, key: &str) -> anyhow::Result<u64>;
+
+    async fn sadd(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool>;
+    async fn srem(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool>;
+    async fn smembers(&self, key: &str) -> anyhow::Result<Vec<Vec<u8>>>;
+    async fn sismember(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool>;
+}
+
+#[cfg(feature = "metrics")]
+#[cfg_attr(
+    not(any(feature = "redis-rs", feature = "redis-fred")),
+    allow(dead_code)
+)]
+pub(crate) async fn timed<T, E>(
+    command: &str,
+    fut: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let start = std::time::Instant::now();
+    let res = fut.await;
+    crate::metrics::record_redis_command(command, start, &res);
+    res
+}
+
+#[cfg(not(feature = "metrics"))]
+#[cfg_attr(
+    not(any(feature = "redis-rs", feature = "redis-fred")),
+    allow(dead_code)
+)]
+pub(crate) async fn timed<T, E>(
+    _command: &str,
+    fut: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    fut.await
+}
+
+static KVSTORE: OnceLock<Arc<dyn KvStore>> = OnceLock::<Arc<dyn KvStore>>::new();
+
+pub fn init_kv_store(store: Arc<dyn KvStore>) -> &'static Arc<dyn KvStore> {
+    KVSTORE.get_or_init(|| store)
+}
+
+pub fn get_kv_store() -> &'static Arc<dyn KvStore> {
+    KVSTORE.get().unwrap()
+}
+
+pub fn try_get_kv_store() -> Option<&'static Arc<dyn KvStore>> {
+    KVSTORE.get()
+}
+
+/// 按启用的feature选择已初始化的redis连接，优先fred，都没有时使用MemoryKvStore
+pub fn default_kv_store() -> Arc<dyn KvStore> {
+    #[cfg(feature = "redis-fred")]
+    if let Some(pool) = crate::redisfred::try_get_redis_pool() {
+        return Arc::new(pool.clone());
+    }
+    #[cfg(feature = "redis-rs")]
+    if let Some(pool) = crate::redis::try_get_redis_pool() {
+        return Arc::new(pool.clone());
+    }
+    tracing::warn!("no redis pool initialized, use MemoryKvStore");
+    Arc::new(MemoryKvStore::default())
+}
@@ -0,0 +1,185 @@
+use super::{timed, KvStore, KvTtl};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::time::Duration;
+
+fn fred_bytes(value: Vec<u8>) -> fred::types::RedisValue {
+    fred::types::RedisValue::Bytes(value.into())
+}
+
+#[async_trait]
+impl KvStore for crate::redisfred::RedisCachePool {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        use fred::interfaces::KeysInterface;
+        Ok(timed("get", self.pool.get(key)).await?)
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        use fred::interfaces::KeysInterface;
+        use fred::types::Expiration::PX;
+        let _: fred::types::RedisValue = timed(
+            "set",
+            self.pool.set(
+                key,
+                fred_bytes(value),
+                ttl.map(|ttl| PX(ttl.as_millis() as i64)),
+                None,
+                false,
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn set_nx(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<bool> {
+        use fred::interfaces::KeysInterface;
+        use fred::types::{Expiration::PX, SetOptions};
+        let res: Option<String> = timed(
+            "set",
+            self.pool.set(
+                key,
+                fred_bytes(value),
+                ttl.map(|ttl| PX(ttl.as_millis() as i64)),
+                Some(SetOptions::NX),
+                false,
+            ),
+        )
+        .await?;
+        Ok(res.is_some())
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<bool> {
+        use fred::interfaces::KeysInterface;
+        let res: u64 = timed("del", self.pool.del(key)).await?;
+        Ok(res > 0)
+    }
+
+    async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        use fred::interfaces::KeysInterface;
+        let res: u64 = timed("exists", self.pool.exists(key)).await?;
+        Ok(res > 0)
+    }
+
+    async fn mget(&self, keys: &[String]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        use fred::interfaces::KeysInterface;
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let values: Vec<fred::types::RedisValue> =
+            timed("mget", self.pool.mget(keys.to_vec())).await?;
+        Ok(values
+            .into_iter()
+            .map(|value| value.into_owned_bytes())
+            .collect())
+    }
+
+    async fn mset(
+        &self,
+        items: Vec<(String, Vec<u8>)>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        use fred::interfaces::{KeysInterface, TransactionInterface};
+        use fred::types::Expiration::PX;
+        if items.is_empty() {
+            return Ok(());
+        }
+        let trx = self.pool.next().multi();
+        for (key, value) in items {
+            let _: () = trx
+                .set(
+                    key,
+                    fred_bytes(value),
+                    ttl.map(|ttl| PX(ttl.as_millis() as i64)),
+                    None,
+                    false,
+                )
+                .await?;
+        }
+        let _: fred::types::RedisValue = timed("mset", trx.exec(true)).await?;
+        Ok(())
+    }
+
+    async fn ttl(&self, key: &str) -> anyhow::Result<KvTtl> {
+        use fred::interfaces::KeysInterface;
+        let pttl: i64 = timed("pttl", self.pool.pttl(key)).await?;
+        Ok(KvTtl::from_pttl(pttl))
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> anyhow::Result<bool> {
+        use fred::interfaces::KeysInterface;
+        Ok(timed(
+            "pexpire",
+            self.pool.pexpire(key, ttl.as_millis() as i64, None),
+        )
+        .await?)
+    }
+
+    async fn hget(&self, key: &str, field: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        use fred::interfaces::HashesInterface;
+        Ok(timed("hget", self.pool.hget(key, field)).await?)
+    }
+
+    async fn hset(&self, key: &str, field: &str, value: Vec<u8>) -> anyhow::Result<()> {
+        use fred::interfaces::HashesInterface;
+        let _: i64 = timed("hset", self.pool.hset(key, (field, fred_bytes(value)))).await?;
+        Ok(())
+    }
+
+    async fn hdel(&self, key: &str, field: &str) -> anyhow::Result<bool> {
+        use fred::interfaces::HashesInterface;
+        let res: u64 = timed("hdel", self.pool.hdel(key, field)).await?;
+        Ok(res > 0)
+    }
+
+    async fn hgetall(&self, key: &str) -> anyhow::Result<HashMap<String, Vec<u8>>> {
+        use fred::interfaces::HashesInterface;
+        Ok(timed("hgetall", self.pool.hgetall(key)).await?)
+    }
+
+    async fn rpush(&self, key: &str, value: Vec<u8>) -> anyhow::Result<u64> {
+        use fred::interfaces::ListInterface;
+        Ok(timed("rpush", self.pool.rpush(key, fred_bytes(value))).await?)
+    }
+
+    async fn lpop(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        use fred::interfaces::ListInterface;
+        Ok(timed("lpop", self.pool.lpop(key, None)).await?)
+    }
+
+    async fn lrange(&self, key: &str, start: i64, stop: i64) -> anyhow::Result<Vec<Vec<u8>>> {
+        use fred::interfaces::ListInterface;
+        Ok(timed("lrange", self.pool.lrange(key, start, stop)).await?)
+    }
+
+    async fn llen(&self, key: &str) -> anyhow::Result<u64> {
+        use fred::interfaces::ListInterface;
+        Ok(timed("llen", self.pool.llen(key)).await?)
+    }
+
+    async fn sadd(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool> {
+        use fred::interfaces::SetsInterface;
+        let res: u64 = timed("sadd", self.pool.sadd(key, fred_bytes(member))).await?;
+        Ok(res > 0)
+    }
+
+    async fn srem(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool> {
+        use fred::interfaces::SetsInterface;
+        let res: u64 = timed("srem", self.pool.srem(key, fred_bytes(member))).await?;
+        Ok(res > 0)
+    }
+
+    async fn smembers(&self, key: &str) -> anyhow::Result<Vec<Vec<u8>>> {
+        use fred::interfaces::SetsInterface;
+        Ok(timed("smembers", self.pool.smembers(key)).await?)
+    }
+
+    async fn sismember(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool> {
+        use fred::interfaces::SetsInterface;
+        Ok(timed("sismember", self.pool.sismember(key, fred_bytes(member))).await?)
+    }
+}
@@ -0,0 +1,184 @@
+use super::{timed, KvStore, KvTtl};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[async_trait]
+impl KvStore for crate::redis::RedisPool {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        Ok(timed("get", connection.get(key)).await?)
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        match ttl {
+            Some(ttl) => {
+                timed(
+                    "set",
+                    connection.pset_ex(key, value, ttl.as_millis() as u64),
+                )
+                .await?
+            }
+            None => timed("set", connection.set(key, value)).await?,
+        }
+        Ok(())
+    }
+
+    async fn set_nx(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<bool> {
+        let mut connection = self.connection.clone();
+        let mut cmd = ::redis::cmd("SET");
+        cmd.arg(key).arg(value).arg("NX");
+        if let Some(ttl) = ttl {
+            cmd.arg("PX").arg(ttl.as_millis() as u64);
+        }
+        let res: Option<String> = timed("set", cmd.query_async(&mut connection)).await?;
+        Ok(res.is_some())
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<bool> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        let res: u64 = timed("del", connection.del(key)).await?;
+        Ok(res > 0)
+    }
+
+    async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        Ok(timed("exists", connection.exists(key)).await?)
+    }
+
+    async fn mget(&self, keys: &[String]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut connection = self.connection.clone();
+        Ok(timed(
+            "mget",
+            ::redis::cmd("MGET").arg(keys).query_async(&mut connection),
+        )
+        .await?)
+    }
+
+    async fn mset(
+        &self,
+        items: Vec<(String, Vec<u8>)>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        if items.is_empty() {
+            return Ok(());
+        }
+        let mut connection = self.connection.clone();
+        let mut pipe = ::redis::pipe();
+        pipe.atomic();
+        for (key, value) in items {
+            match ttl {
+                Some(ttl) => pipe.pset_ex(key, value, ttl.as_millis() as u64).ignore(),
+                None => pipe.set(key, value).ignore(),
+            };
+        }
+        timed("mset", pipe.query_async::<()>(&mut connection)).await?;
+        Ok(())
+    }
+
+    async fn ttl(&self, key: &str) -> anyhow::Result<KvTtl> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        let pttl: i64 = timed("pttl", connection.pttl(key)).await?;
+        Ok(KvTtl::from_pttl(pttl))
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> anyhow::Result<bool> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        Ok(timed("pexpire", connection.pexpire(key, ttl.as_millis() as i64)).await?)
+    }
+
+    async fn hget(&self, key: &str, field: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        Ok(timed("hget", connection.hget(key, field)).await?)
+    }
+
+    async fn hset(&self, key: &str, field: &str, value: Vec<u8>) -> anyhow::Result<()> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        let _: i64 = timed("hset", connection.hset(key, field, value)).await?;
+        Ok(())
+    }
+
+    async fn hdel(&self, key: &str, field: &str) -> anyhow::Result<bool> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        let res: u64 = timed("hdel", connection.hdel(key, field)).await?;
+        Ok(res > 0)
+    }
+
+    async fn hgetall(&self, key: &str) -> anyhow::Result<HashMap<String, Vec<u8>>> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        Ok(timed("hgetall", connection.hgetall(key)).await?)
+    }
+
+    async fn rpush(&self, key: &str, value: Vec<u8>) -> anyhow::Result<u64> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        Ok(timed("rpush", connection.rpush(key, value)).await?)
+    }
+
+    async fn lpop(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        Ok(timed("lpop", connection.lpop(key, None)).await?)
+    }
+
+    async fn lrange(&self, key: &str, start: i64, stop: i64) -> anyhow::Result<Vec<Vec<u8>>> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        Ok(timed(
+            "lrange",
+            connection.lrange(key, start as isize, stop as isize),
+        )
+        .await?)
+    }
+
+    async fn llen(&self, key: &str) -> anyhow::Result<u64> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        Ok(timed("llen", connection.llen(key)).await?)
+    }
+
+    async fn sadd(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        let res: u64 = timed("sadd", connection.sadd(key, member)).await?;
+        Ok(res > 0)
+    }
+
+    async fn srem(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        let res: u64 = timed("srem", connection.srem(key, member)).await?;
+        Ok(res > 0)
+    }
+
+    async fn smembers(&self, key: &str) -> anyhow::Result<Vec<Vec<u8>>> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        Ok(timed("smembers", connection.smembers(key)).await?)
+    }
+
+    async fn sismember(&self, key: &str, member: Vec<u8>) -> anyhow::Result<bool> {
+        use ::redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        Ok(timed("sismember", connection.sismember(key, member)).await?)
+    }
+}
@@ -1,24 +1,53 @@
 extern crate utoipa;
+// 测试中使用webbase_macros生成的::webbase::路径
+#[cfg(test)]
+extern crate self as webbase;
 pub mod db_datatime;
 pub mod db_password;
 
 pub use db_datatime::*;
 pub use db_password::*;
 
+pub mod crud;
+pub mod db;
+#[cfg(feature = "migrate")]
+pub mod migrate;
 pub mod mysql;
 pub use mysql::*;
 
 pub mod permission;
+#[doc(hidden)]
+pub use async_trait;
+#[doc(hidden)]
+pub use inventory;
 pub use permission::*;
+pub use webbase_macros::permission;
+
+pub mod openapi;
 
 pub mod rbac;
 pub use rbac::*;
 
+pub mod cache;
+pub mod kv;
+pub mod lock;
+#[cfg(feature = "redis-rs")]
 pub mod redis;
+#[cfg(any(feature = "redis-rs", feature = "redis-fred"))]
+pub mod redisconfig;
+#[cfg(feature = "redis-fred")]
 pub mod redisfred;
 
+#[cfg(feature = "redis-fred")]
+pub mod eventbus;
+#[cfg(feature = "redis-fred")]
+pub mod jobqueue;
+
+pub mod middleware;
 pub mod response;
-pub use response::{ClientRsp, NoneBodyData, Response};
+pub use response::{
+    set_http_status_mode, AppError, ClientRsp, NoneBodyData, Page, PageQuery, Response,
+};
 
 pub mod token;
 pub use token::*;
@@ -28,6 +57,9 @@ pub use token_rsa::*;
 pub mod client;
 pub use client::*;
 
+pub mod apiclient;
+pub use apiclient::{ApiClient, ApiError};
+
 #[cfg(feature = "influx")]
 pub mod influx;
 #[cfg(feature = "influx")]
@@ -37,10 +69,16 @@ pub mod idmlogin;
 pub use idmlogin::*;
 
 pub mod common;
+pub mod health;
 pub mod sysinfo;
 pub mod time_util;
 
 #[cfg(feature = "kafka")]
 pub mod kafka;
 
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
 pub mod keycenter;
+pub mod keycenter_server;
+pub mod idm_server;
@@ -0,0 +1,536 @@
+use async_trait::async_trait;
+use rand::Rng;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "redis-fred")]
+use crate::redisfred::CachedScript;
+
+// 加锁成功时返回递增的fencing token，失败返回0
+#[cfg(any(feature = "redis-rs", feature = "redis-fred"))]
+const ACQUIRE_SCRIPT: &str = r#"
+if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return redis.call('INCR', KEYS[2])
+end
+return 0
+"#;
+
+#[cfg(any(feature = "redis-rs", feature = "redis-fred"))]
+const EXTEND_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+#[cfg(any(feature = "redis-rs", feature = "redis-fred"))]
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+// fred连接池使用EVALSHA执行，redis-rs的Script自带sha缓存
+#[cfg(feature = "redis-fred")]
+static FRED_ACQUIRE_SCRIPT: CachedScript = CachedScript::new(ACQUIRE_SCRIPT);
+#[cfg(feature = "redis-fred")]
+static FRED_EXTEND_SCRIPT: CachedScript = CachedScript::new(EXTEND_SCRIPT);
+#[cfg(feature = "redis-fred")]
+static FRED_RELEASE_SCRIPT: CachedScript = CachedScript::new(RELEASE_SCRIPT);
+
+// 与redlock相同，按ttl的1%估算各节点之间的时钟漂移
+const CLOCK_DRIFT_FACTOR: f64 = 0.01;
+
+/// 一个独立的redis节点，redis和fred两种连接方式分别实现
+#[async_trait]
+pub trait LockNode: Send + Sync {
+    /// 加锁成功返回fencing token
+    async fn acquire(
+        &self,
+        key: &str,
+        fence_key: &str,
+        value: &str,
+        ttl: Duration,
+    ) -> anyhow::Result<Option<u64>>;
+    async fn extend(&self, key: &str, value: &str, ttl: Duration) -> anyhow::Result<bool>;
+    async fn release(&self, key: &str, value: &str) -> anyhow::Result<bool>;
+}
+
+#[cfg(feature = "redis-rs")]
+#[async_trait]
+impl LockNode for ::redis::aio::MultiplexedConnection {
+    async fn acquire(
+        &self,
+        key: &str,
+        fence_key: &str,
+        value: &str,
+        ttl: Duration,
+    ) -> anyhow::Result<Option<u64>> {
+        let mut connection = self.clone();
+        let token: u64 = ::redis::Script::new(ACQUIRE_SCRIPT)
+            .key(key)
+            .key(fence_key)
+            .arg(value)
+            .arg(ttl.as_millis() as u64)
+            .invoke_async(&mut connection)
+            .await?;
+        Ok(Some(token).filter(|token| *token > 0))
+    }
+
+    async fn extend(&self, key: &str, value: &str, ttl: Duration) -> anyhow::Result<bool> {
+        let mut connection = self.clone();
+        let res: i64 = ::redis::Script::new(EXTEND_SCRIPT)
+            .key(key)
+            .arg(value)
+            .arg(ttl.as_millis() as u64)
+            .invoke_async(&mut connection)
+            .await?;
+        Ok(res == 1)
+    }
+
+    async fn release(&self, key: &str, value: &str) -> anyhow::Result<bool> {
+        let mut connection = self.clone();
+        let res: i64 = ::redis::Script::new(RELEASE_SCRIPT)
+            .key(key)
+            .arg(value)
+            .invoke_async(&mut connection)
+            .await?;
+        Ok(res == 1)
+    }
+}
+
+#[cfg(feature = "redis-fred")]
+#[async_trait]
+impl LockNode for fred::clients::RedisPool {
+    async fn acquire(
+        &self,
+        key: &str,
+        fence_key: &str,
+        value: &str,
+        ttl: Duration,
+    ) -> anyhow::Result<Option<u64>> {
+        let token: u64 = FRED_ACQUIRE_SCRIPT
+            .eval(
+                self,
+                vec![key, fence_key],
+                vec![value.to_string(), ttl.as_millis().to_string()],
+            )
+            .await?;
+        Ok(Some(token).filter(|token| *token > 0))
+    }
+
+    async fn extend(&self, key: &str, value: &str, ttl: Duration) -> anyhow::Result<bool> {
+        let res: i64 = FRED_EXTEND_SCRIPT
+            .eval(
+                self,
+                key,
+                vec![value.to_string(), ttl.as_millis().to_string()],
+            )
+            .await?;
+        Ok(res == 1)
+    }
+
+    async fn release(&self, key: &str, value: &str) -> anyhow::Result<bool> {
+        let res: i64 = FRED_RELEASE_SCRIPT.eval(self, key, value).await?;
+        Ok(res == 1)
+    }
+}
+
+#[derive(Clone)]
+struct LockInner {
+    nodes: Vec<Arc<dyn LockNode>>,
+    quorum: usize,
+    prefix: String,
+    retry_delay: Duration,
+    wait_timeout: Duration,
+    watchdog: bool,
+}
+
+impl LockInner {
+    async fn release_all(&self, key: &str, value: &str) {
+        let results =
+            futures::future::join_all(self.nodes.iter().map(|node| node.release(key, value))).await;
+        for e in results.into_iter().filter_map(|res| res.err()) {
+            tracing::warn!("release lock {} error: {:?}", key, e);
+        }
+    }
+
+    /// 超过半数节点续期成功才算成功
+    async fn extend_all(&self, key: &str, value: &str, ttl: Duration) -> bool {
+        let results =
+            futures::future::join_all(self.nodes.iter().map(|node| node.extend(key, value, ttl)))
+                .await;
+        results
+            .into_iter()
+            .filter(|res| matches!(res, Ok(true)))
+            .count()
+            >= self.quorum
+    }
+}
+
+/// 基于RedLock算法的异步分布式锁，多个独立的redis节点时超过半数加锁成功才算成功，
+/// fencing token只在单节点时提供，多个节点的计数器相互独立，无法保证递增
+/// - `let lock = DistributedLock::fred();`
+/// - `let guard = lock.lock("order:1", Duration::from_secs(10)).await?;`
+/// - `lock.with_lock("order:1", Duration::from_secs(10), |token| async move { ... }).await?;`
+#[derive(Clone)]
+pub struct DistributedLock {
+    inner: Arc<LockInner>,
+}
+
+impl DistributedLock {
+    pub fn new(nodes: Vec<Arc<dyn LockNode>>) -> Self {
+        let quorum = nodes.len() / 2 + 1;
+        Self {
+            inner: Arc::new(LockInner {
+                nodes,
+                quorum,
+                prefix: "lock".into(),
+                retry_delay: Duration::from_millis(200),
+                wait_timeout: Duration::from_secs(10),
+                watchdog: true,
+            }),
+        }
+    }
+
+    /// 使用redisfred::init_redis_pool初始化的连接池
+    #[cfg(feature = "redis-fred")]
+    pub fn fred() -> Self {
+        Self::from_fred_pool(crate::redisfred::get_redis_pool())
+    }
+
+    #[cfg(feature = "redis-fred")]
+    pub fn from_fred_pool(pool: &crate::redisfred::RedisCachePool) -> Self {
+        Self::new(vec![Arc::new(pool.pool.clone())])
+    }
+
+    #[cfg(feature = "redis-rs")]
+    pub fn from_redis_pool(pool: &crate::redis::RedisPool) -> Self {
+        Self::new(vec![Arc::new(pool.connection.clone())])
+    }
+
+    /// 连接多个相互独立的redis节点(非主从或集群)，url格式与init_redis_pool相同
+    #[cfg(feature = "redis-rs")]
+    pub async fn connect(redis_urls: &[String]) -> anyhow::Result<Self> {
+        let mut nodes: Vec<Arc<dyn LockNode>> = Vec::new();
+        for url in redis_urls {
+            let client = ::redis::Client::open(url.as_str())?;
+            nodes.push(Arc::new(client.get_multiplexed_async_connection().await?));
+        }
+        Ok(Self::new(nodes))
+    }
+
+    /// 连接redlock配置中的节点
+    #[cfg(feature = "redis-rs")]
+    pub async fn from_config(config: &crate::redisconfig::RedisPoolConfig) -> anyhow::Result<Self> {
+        Self::connect(&config.redlock_nodes()?).await
+    }
+
+    /// 已被clone时复制一份配置，不影响其他的DistributedLock
+    fn inner_mut(&mut self) -> &mut LockInner {
+        Arc::make_mut(&mut self.inner)
+    }
+
+    /// redis key的前缀，实际的key为`{prefix}:{key}`
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.inner_mut().prefix = prefix.to_string();
+        self
+    }
+
+    /// lock等待锁时每次重试的最大间隔和最长等待时间
+    pub fn with_retry(mut self, retry_delay: Duration, wait_timeout: Duration) -> Self {
+        let inner = self.inner_mut();
+        inner.retry_delay = retry_delay;
+        inner.wait_timeout = wait_timeout;
+        self
+    }
+
+    /// 是否在持有锁期间每ttl/3自动续期，默认开启
+    pub fn with_watchdog(mut self, watchdog: bool) -> Self {
+        self.inner_mut().watchdog = watchdog;
+        self
+    }
+
+    /// 尝试加锁一次，锁已被占用时返回None，ttl至少为1毫秒
+    pub async fn try_lock(&self, key: &str, ttl: Duration) -> anyhow::Result<Option<LockGuard>> {
+        if ttl < Duration::from_millis(1) {
+            return Err(anyhow::anyhow!("锁{}的ttl不能小于1毫秒-{:?}", key, ttl));
+        }
+        let inner = &self.inner;
+        // hash tag保证锁和fencing token的key在集群中位于同一个slot
+        let key = format!("{}:{{{}}}", inner.prefix, key);
+        let fence_key = format!("{}:fence", key);
+        let value = uuid::Uuid::new_v4().simple().to_string();
+
+        let start = Instant::now();
+        let results = futures::future::join_all(
+            inner
+                .nodes
+                .iter()
+                .map(|node| node.acquire(&key, &fence_key, &value, ttl)),
+        )
+        .await;
+        let mut errors = Vec::new();
+        let mut tokens = Vec::new();
+        for res in results {
+            match res {
+                Ok(Some(token)) => tokens.push(token),
+                Ok(None) => {}
+                Err(e) => errors.push(e),
+            }
+        }
+        let drift = ttl.mul_f64(CLOCK_DRIFT_FACTOR) + Duration::from_millis(2);
+        let validity = ttl.saturating_sub(start.elapsed() + drift);
+        if tokens.len() >= inner.quorum && !validity.is_zero() {
+            let mut guard = LockGuard {
+                lock: inner.clone(),
+                key,
+                value,
+                // 每个节点有各自的计数器，不同的多数派之间取最大值不能保证递增
+                fencing_token: match inner.nodes.len() {
+                    1 => tokens.first().copied(),
+                    _ => None,
+                },
+                lost: Arc::new(AtomicBool::new(false)),
+                watchdog: None,
+                released: false,
+            };
+            if inner.watchdog {
+                guard.start_watchdog(ttl);
+            }
+            return Ok(Some(guard));
+        }
+
+        inner.release_all(&key, &value).await;
+        if errors.len() > inner.nodes.len() - inner.quorum {
+            // 出错的节点过多，无论锁是否被占用都不可能加锁成功
+            return Err(errors.remove(0).context(format!("lock {} error", key)));
+        }
+        Ok(None)
+    }
+
+    /// 加锁，锁被占用时随机等待后重试，超过wait_timeout返回错误
+    pub async fn lock(&self, key: &str, ttl: Duration) -> anyhow::Result<LockGuard> {
+        let deadline = Instant::now() + self.inner.wait_timeout;
+        loop {
+            if let Some(guard) = self.try_lock(key, ttl).await? {
+                return Ok(guard);
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow::anyhow!("获取锁{}超时", key));
+            }
+            let delay = rand::thread_rng().gen_range(0..=self.inner.retry_delay.as_millis() as u64);
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+        }
+    }
+
+    /// 持有锁执行f，参数为fencing token(多节点时为None)，执行完成后释放锁
+    pub async fn with_lock<T, F, Fut>(&self, key: &str, ttl: Duration, f: F) -> anyhow::Result<T>
+    where
+        F: FnOnce(Option<u64>) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let guard = self.lock(key, ttl).await?;
+        let result = f(guard.fencing_token()).await;
+        if guard.is_lost() {
+            tracing::warn!("lock {} was lost before the task finished", guard.key());
+        }
+        guard.release().await;
+        result
+    }
+}
+
+/// 持有的锁，drop时自动释放
+pub struct LockGuard {
+    lock: Arc<LockInner>,
+    key: String,
+    value: String,
+    fencing_token: Option<u64>,
+    lost: Arc<AtomicBool>,
+    watchdog: Option<tokio::task::JoinHandle<()>>,
+    released: bool,
+}
+
+impl LockGuard {
+    /// 实际使用的redis key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 每次加锁成功都会递增，写入下游时携带以拒绝过期持有者的写入，
+    /// 只有单个redis节点时才有全局递增的计数器，多节点时为None
+    pub fn fencing_token(&self) -> Option<u64> {
+        self.fencing_token
+    }
+
+    /// 自动续期失败，锁可能已被其他人持有
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// 手动续期，ttl小于1毫秒时不续期并返回false
+    pub async fn extend(&self, ttl: Duration) -> bool {
+        if ttl < Duration::from_millis(1) {
+            tracing::warn!("extend lock {} with invalid ttl {:?}", self.key, ttl);
+            return false;
+        }
+        let extended = self.lock.extend_all(&self.key, &self.value, ttl).await;
+        if !extended {
+            self.lost.store(true, Ordering::Relaxed);
+        }
+        extended
+    }
+
+    pub async fn release(mut self) {
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog.abort();
+        }
+        self.released = true;
+        self.lock.release_all(&self.key, &self.value).await;
+    }
+
+    fn start_watchdog(&mut self, ttl: Duration) {
+        let lock = self.lock.clone();
+        let key = self.key.clone();
+        let value = self.value.clone();
+        let lost = self.lost.clone();
+        self.watchdog = Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ttl / 3);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if !lock.extend_all(&key, &value, ttl).await {
+                    tracing::warn!("extend lock {} failed", key);
+                    lost.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+        }));
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog.abort();
+        }
+        if self.released {
+            return;
+        }
+        // drop中不能await，在当前的tokio runtime中异步释放，锁最迟在ttl后过期
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let lock = self.lock.clone();
+            let key = std::mem::take(&mut self.key);
+            let value = std::mem::take(&mut self.value);
+            handle.spawn(async move { lock.release_all(&key, &value).await });
+        }
+    }
+}
+
+#[cfg(test)]
+mod lock_tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::Mutex;
+
+    /// 内存中的节点，不处理过期时间
+    #[derive(Default)]
+    struct MemoryNode {
+        keys: Mutex<HashMap<String, String>>,
+        fence: AtomicU64,
+    }
+
+    #[async_trait]
+    impl LockNode for MemoryNode {
+        async fn acquire(
+            &self,
+            key: &str,
+            _fence_key: &str,
+            value: &str,
+            _ttl: Duration,
+        ) -> anyhow::Result<Option<u64>> {
+            let mut keys = self.keys.lock().unwrap();
+            if keys.contains_key(key) {
+                return Ok(None);
+            }
+            keys.insert(key.to_string(), value.to_string());
+            Ok(Some(self.fence.fetch_add(1, Ordering::SeqCst) + 1))
+        }
+
+        async fn extend(&self, key: &str, value: &str, _ttl: Duration) -> anyhow::Result<bool> {
+            Ok(self.keys.lock().unwrap().get(key).map(String::as_str) == Some(value))
+        }
+
+        async fn release(&self, key: &str, value: &str) -> anyhow::Result<bool> {
+            let mut keys = self.keys.lock().unwrap();
+            if keys.get(key).map(String::as_str) == Some(value) {
+                keys.remove(key);
+                return Ok(true);
+            }
+            Ok(false)
+        }
+    }
+
+    fn memory_lock(nodes: usize) -> DistributedLock {
+        DistributedLock::new(
+            (0..nodes)
+                .map(|_| Arc::new(MemoryNode::default()) as Arc<dyn LockNode>)
+                .collect(),
+        )
+        .with_watchdog(false)
+    }
+
+    #[tokio::test]
+    async fn fencing_token_test() {
+        let lock = memory_lock(1);
+        let guard = lock.try_lock("a", Duration::from_secs(10)).await.unwrap();
+        let guard = guard.unwrap();
+        assert_eq!(guard.fencing_token(), Some(1));
+        assert!(!guard.extend(Duration::from_micros(500)).await);
+        assert!(!guard.is_lost());
+        // ttl小于1毫秒时watchdog的续期间隔为0，直接拒绝
+        assert!(lock
+            .try_lock("z", Duration::from_micros(500))
+            .await
+            .is_err());
+        assert!(lock
+            .try_lock("a", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .is_none());
+        guard.release().await;
+        let guard = lock.try_lock("a", Duration::from_secs(10)).await.unwrap();
+        assert_eq!(guard.unwrap().fencing_token(), Some(2));
+
+        // 多个节点的计数器相互独立，不提供fencing token
+        let lock = memory_lock(3);
+        let guard = lock.try_lock("a", Duration::from_secs(10)).await.unwrap();
+        assert_eq!(guard.unwrap().fencing_token(), None);
+    }
+
+    #[tokio::test]
+    async fn lock_wait_test() {
+        let lock = memory_lock(3);
+        // clone之后修改配置不影响原来的DistributedLock
+        let waiting = lock
+            .clone()
+            .with_retry(Duration::from_millis(10), Duration::from_millis(50));
+        let guard = lock.lock("b", Duration::from_secs(10)).await.unwrap();
+        assert!(waiting.lock("b", Duration::from_secs(10)).await.is_err());
+        guard.release().await;
+        let value = waiting
+            .with_lock("b", Duration::from_secs(10), |_| async { Ok(1) })
+            .await
+            .unwrap();
+        assert_eq!(value, 1);
+        assert!(lock
+            .try_lock("b", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
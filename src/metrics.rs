@@ -0,0 +1,215 @@
+use actix_web::HttpResponse;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::OnceLock;
+
+/// webbase各组件使用的Prometheus指标，业务也可以向registry注册自己的指标
+pub struct Metrics {
+    pub registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_duration: HistogramVec,
+    pub auth_decisions: IntCounterVec,
+    pub rbac_version: IntGauge,
+    pub rbac_age: IntGauge,
+    pub redis_duration: HistogramVec,
+    pub redis_errors: IntCounterVec,
+    pub mysql_connections: IntGaugeVec,
+    pub db_transactions: IntCounterVec,
+    pub db_transaction_duration: HistogramVec,
+    pub kafka_messages: IntCounterVec,
+    pub kafka_errors: IntCounterVec,
+    pub kafka_consumer_lag: IntGaugeVec,
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::<Metrics>::new();
+
+pub fn get_metrics() -> &'static Metrics {
+    METRICS.get_or_init(|| Metrics::new(Registry::new()).unwrap())
+}
+
+impl Metrics {
+    pub fn new(registry: Registry) -> prometheus::Result<Self> {
+        let metrics = Self {
+            http_requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "HTTP requests"),
+                &["method", "route", "code"],
+            )?,
+            http_duration: HistogramVec::new(
+                HistogramOpts::new("http_request_duration_seconds", "HTTP request latency"),
+                &["method", "route"],
+            )?,
+            auth_decisions: IntCounterVec::new(
+                Opts::new("auth_decisions_total", "PermissionMap auth decisions"),
+                &["check", "result"],
+            )?,
+            rbac_version: IntGauge::new("rbac_cache_version", "RBAC cache update count")?,
+            rbac_age: IntGauge::new(
+                "rbac_cache_age_seconds",
+                "seconds since the RBAC cache was updated",
+            )?,
+            redis_duration: HistogramVec::new(
+                HistogramOpts::new("redis_command_duration_seconds", "Redis command latency")
+                    .buckets(vec![
+                        0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+                    ]),
+                &["command"],
+            )?,
+            redis_errors: IntCounterVec::new(
+                Opts::new("redis_command_errors_total", "Redis command errors"),
+                &["command"],
+            )?,
+            mysql_connections: IntGaugeVec::new(
+                Opts::new("mysql_pool_connections", "MySQL pool connections"),
+                &["state"],
+            )?,
+            db_transactions: IntCounterVec::new(
+                Opts::new("db_transactions_total", "Database transactions"),
+                &["result"],
+            )?,
+            db_transaction_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "db_transaction_duration_seconds",
+                    "Database transaction latency including retries",
+                ),
+                &["result"],
+            )?,
+            kafka_messages: IntCounterVec::new(
+                Opts::new(
+                    "kafka_messages_total",
+                    "Kafka messages produced or consumed",
+                ),
+                &["direction", "topic"],
+            )?,
+            kafka_errors: IntCounterVec::new(
+                Opts::new("kafka_errors_total", "Kafka producer and consumer errors"),
+                &["direction"],
+            )?,
+            kafka_consumer_lag: IntGaugeVec::new(
+                Opts::new("kafka_consumer_lag", "Kafka consumer lag per partition"),
+                &["topic", "partition"],
+            )?,
+            registry,
+        };
+        metrics
+            .registry
+            .register(Box::new(metrics.http_requests.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.http_duration.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.auth_decisions.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.rbac_version.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.rbac_age.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.redis_duration.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.redis_errors.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.mysql_connections.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.db_transactions.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.db_transaction_duration.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.kafka_messages.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.kafka_errors.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.kafka_consumer_lag.clone()))?;
+        Ok(metrics)
+    }
+
+    /// 连接池和缓存状态类的指标在抓取时读取
+    fn refresh_gauges(&self) {
+        if let Some(rbac) = crate::rbac::try_get_rbac() {
+            let (version, updated_at) = rbac.version();
+            self.rbac_version.set(version as i64);
+            let now = chrono::Utc::now().timestamp() as u64;
+            self.rbac_age.set(now.saturating_sub(updated_at) as i64);
+        }
+        #[cfg(feature = "mysql")]
+        if let Some(conn) = crate::mysql::try_get_mysql().filter(|conn| {
+            sea_orm::ConnectionTrait::get_database_backend(*conn) == sea_orm::DbBackend::MySql
+        }) {
+            let pool = conn.get_mysql_connection_pool();
+            let size = pool.size() as i64;
+            let idle = pool.num_idle() as i64;
+            self.mysql_connections
+                .with_label_values(&["total"])
+                .set(size);
+            self.mysql_connections
+                .with_label_values(&["idle"])
+                .set(idle);
+            self.mysql_connections
+                .with_label_values(&["in_use"])
+                .set(size - idle);
+        }
+    }
+
+    pub fn gather_text(&self) -> String {
+        self.refresh_gauges();
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("encode metrics error: {:?}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+pub(crate) fn record_auth_decision<T>(check: &str, result: &anyhow::Result<T>) {
+    let result = if result.is_ok() { "allow" } else { "deny" };
+    get_metrics()
+        .auth_decisions
+        .with_label_values(&[check, result])
+        .inc();
+}
+
+pub(crate) fn record_redis_command<T, E>(
+    command: &str,
+    start: std::time::Instant,
+    result: &Result<T, E>,
+) {
+    let metrics = get_metrics();
+    metrics
+        .redis_duration
+        .with_label_values(&[command])
+        .observe(start.elapsed().as_secs_f64());
+    if result.is_err() {
+        metrics.redis_errors.with_label_values(&[command]).inc();
+    }
+}
+
+/// result为commit、rollback或retry，retry只计数
+pub(crate) fn record_db_transaction(result: &str, start: Option<std::time::Instant>) {
+    let metrics = get_metrics();
+    metrics.db_transactions.with_label_values(&[result]).inc();
+    if let Some(start) = start {
+        metrics
+            .db_transaction_duration
+            .with_label_values(&[result])
+            .observe(start.elapsed().as_secs_f64());
+    }
+}
+
+/// Prometheus抓取接口
+/// - `App::new().route("/metrics", web::get().to(metrics_handler))`
+pub async fn metrics_handler() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(get_metrics().gather_text())
+}
@@ -0,0 +1,63 @@
+use crate::db::with_actor;
+use crate::permission::try_get_pmap;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+/// 解析请求中的AccessToken作为当前操作者，#[derive(Audited)]的实体保存时用于填充created_by和updated_by，
+/// 只解析token不做权限检查，没有token或解析失败时不设置操作者
+/// - `App::new().wrap(AuditActor)`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AuditActor;
+
+impl<S, B> Transform<S, ServiceRequest> for AuditActor
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AuditActorMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuditActorMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AuditActorMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuditActorMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        Box::pin(async move {
+            let access = match try_get_pmap() {
+                Some(pmap) => pmap.decode_request_token(req.request()).await.ok(),
+                None => None,
+            };
+            match access {
+                Some(access) => with_actor(access, service.call(req)).await,
+                None => service.call(req).await,
+            }
+        })
+    }
+}
@@ -0,0 +1,78 @@
+use crate::metrics::get_metrics;
+use crate::response::EnvelopeCode;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+use std::time::Instant;
+
+/// 记录HTTP请求数和耗时，route使用匹配的路由模板，code优先使用Response中的code
+/// - `App::new().wrap(RequestMetrics)`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware { service }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let method = req.method().to_string();
+        // 未匹配的路由统一计数，避免路径作为label导致指标数量失控
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let result = fut.await;
+            let code = match &result {
+                Ok(res) => res
+                    .response()
+                    .extensions()
+                    .get::<EnvelopeCode>()
+                    .map(|code| code.0)
+                    .unwrap_or(res.status().as_u16()),
+                Err(e) => e.as_response_error().status_code().as_u16(),
+            };
+            let metrics = get_metrics();
+            metrics
+                .http_requests
+                .with_label_values(&[method.as_str(), route.as_str(), &code.to_string()])
+                .inc();
+            metrics
+                .http_duration
+                .with_label_values(&[method.as_str(), route.as_str()])
+                .observe(start.elapsed().as_secs_f64());
+            result
+        })
+    }
+}
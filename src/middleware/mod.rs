@@ -0,0 +1,11 @@
+pub mod actor;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod ratelimit;
+pub mod request_log;
+
+pub use actor::AuditActor;
+#[cfg(feature = "metrics")]
+pub use metrics::RequestMetrics;
+pub use ratelimit::{RateLimit, RateLimitAlgorithm, RateLimitKey, RateLimiter};
+pub use request_log::{current_request_id, RequestId, RequestLog, TraceParent};
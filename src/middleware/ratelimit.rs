@@ -0,0 +1,498 @@
+use crate::permission::try_get_pmap;
+#[cfg(feature = "redis-fred")]
+use crate::redisfred::{lua_now_millis, CachedScript};
+use crate::time_util::get_now_millis;
+use crate::{NoneBodyData, Response};
+use actix_http::header::{HeaderValue, RETRY_AFTER};
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpRequest};
+use dashmap::DashMap;
+use futures_util::future::LocalBoxFuture;
+use std::collections::VecDeque;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// 限流的维度
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitKey {
+    User,  // AccessToken中的user_account，无token时按IP
+    App,   // AccessToken中的app_id，无token时按IP
+    Ip,    // 连接的对端IP，RateLimiter::with_trusted_proxy时取Forwarded/X-Forwarded-For
+    Route, // 整个路由共用一个额度
+}
+
+#[derive(Clone, Debug)]
+pub enum RateLimitAlgorithm {
+    /// window时间内最多limit次请求
+    SlidingWindow { limit: u64, window: Duration },
+    /// 桶容量capacity，每秒补充refill_per_second个令牌，refill_per_second必须大于0
+    TokenBucket {
+        capacity: u64,
+        refill_per_second: f64,
+    },
+}
+
+/// 限流规则，可配置在PermissionMap::ratelimit中按路由生效
+/// - `get_pmap().ratelimit.insert("POST /api/v1/login", RateLimit::sliding_window(RateLimitKey::Ip, 10, Duration::from_secs(60)))`
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    pub key: RateLimitKey,
+    pub algorithm: RateLimitAlgorithm,
+}
+
+impl RateLimit {
+    pub fn sliding_window(key: RateLimitKey, limit: u64, window: Duration) -> Self {
+        Self {
+            key,
+            algorithm: RateLimitAlgorithm::SlidingWindow { limit, window },
+        }
+    }
+
+    /// refill_per_second必须大于0，否则无法计算等待时间和过期时间
+    pub fn token_bucket(
+        key: RateLimitKey,
+        capacity: u64,
+        refill_per_second: f64,
+    ) -> anyhow::Result<Self> {
+        let algorithm = RateLimitAlgorithm::TokenBucket {
+            capacity,
+            refill_per_second,
+        };
+        algorithm.validate()?;
+        Ok(Self { key, algorithm })
+    }
+}
+
+impl RateLimitAlgorithm {
+    /// 检查参数，window为0或令牌补充速率不大于0时无法计算等待时间和过期时间
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            RateLimitAlgorithm::SlidingWindow { window, .. } if window.as_millis() == 0 => {
+                Err(anyhow::anyhow!("无效的限流窗口-{:?}", window))
+            }
+            RateLimitAlgorithm::TokenBucket {
+                refill_per_second, ..
+            } if !(*refill_per_second > 0.0 && refill_per_second.is_finite()) => {
+                Err(anyhow::anyhow!("无效的令牌补充速率-{}", refill_per_second))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub remaining: u64,
+    pub retry_after: Duration, // 被拒绝时需要等待的时间
+}
+
+impl RateLimitKey {
+    /// trusted_proxy为false时只使用连接的对端地址，客户端可以任意伪造Forwarded/X-Forwarded-For
+    async fn subject(&self, req: &HttpRequest, trusted_proxy: bool) -> String {
+        let ip = || {
+            let ip = if trusted_proxy {
+                req.connection_info()
+                    .realip_remote_addr()
+                    .map(|addr| addr.to_string())
+            } else {
+                req.peer_addr().map(|addr| addr.ip().to_string())
+            };
+            format!("ip:{}", ip.as_deref().unwrap_or("-"))
+        };
+        match self {
+            RateLimitKey::User | RateLimitKey::App => {
+                let access = match try_get_pmap() {
+                    Some(pmap) => pmap.decode_request_token(req).await.ok(),
+                    None => None,
+                };
+                match access {
+                    Some(access) if *self == RateLimitKey::User => {
+                        format!("user:{}", access.user_account)
+                    }
+                    Some(access) => format!("app:{}", access.app_id),
+                    None => ip(),
+                }
+            }
+            RateLimitKey::Ip => ip(),
+            RateLimitKey::Route => "route".into(),
+        }
+    }
+}
+
+#[cfg(feature = "redis-fred")]
+static SLIDING_WINDOW_SCRIPT: CachedScript = CachedScript::new(concat!(
+    lua_now_millis!(),
+    r#"
+local window = tonumber(ARGV[1])
+local limit = tonumber(ARGV[2])
+redis.call('ZREMRANGEBYSCORE', KEYS[1], 0, now - window)
+local count = redis.call('ZCARD', KEYS[1])
+if count < limit then
+    redis.call('ZADD', KEYS[1], now, ARGV[3])
+    redis.call('PEXPIRE', KEYS[1], window)
+    return {1, limit - count - 1, 0}
+end
+local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+local retry = window
+if oldest[2] then
+    retry = tonumber(oldest[2]) + window - now
+end
+return {0, 0, retry}
+"#
+));
+
+#[cfg(feature = "redis-fred")]
+static TOKEN_BUCKET_SCRIPT: CachedScript = CachedScript::new(concat!(
+    lua_now_millis!(),
+    r#"
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2]) / 1000
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or capacity
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate)
+local allowed = 0
+local retry = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+else
+    retry = math.ceil((1 - tokens) / rate)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / rate) + 1000)
+return {allowed, math.floor(tokens), retry}
+"#
+));
+
+/// 检查并消耗一次额度，redisfred未初始化或redis出错时使用本进程内存计数，
+/// 参数无效的规则记录错误后不限流
+pub async fn check_rate_limit(key: &str, algorithm: &RateLimitAlgorithm) -> RateLimitDecision {
+    if let Err(e) = algorithm.validate() {
+        tracing::error!("rate limit {} ignored: {:?}", key, e);
+        return RateLimitDecision {
+            allowed: true,
+            remaining: 0,
+            retry_after: Duration::ZERO,
+        };
+    }
+    #[cfg(feature = "redis-fred")]
+    if let Some(redis) = crate::redisfred::try_get_redis_pool() {
+        match check_redis(redis, key, algorithm).await {
+            Ok(decision) => return decision,
+            Err(e) => tracing::warn!("redis rate limit error, use memory instead: {:?}", e),
+        }
+    }
+    MEMORY_LIMITER
+        .get_or_init(MemoryLimiter::default)
+        .check(key, algorithm, get_now_millis())
+}
+
+#[cfg(feature = "redis-fred")]
+async fn check_redis(
+    redis: &crate::redisfred::RedisCachePool,
+    key: &str,
+    algorithm: &RateLimitAlgorithm,
+) -> anyhow::Result<RateLimitDecision> {
+    let (script, args) = match algorithm {
+        RateLimitAlgorithm::SlidingWindow { limit, window } => (
+            &SLIDING_WINDOW_SCRIPT,
+            vec![
+                window.as_millis().to_string(),
+                limit.to_string(),
+                uuid::Uuid::new_v4().simple().to_string(),
+            ],
+        ),
+        RateLimitAlgorithm::TokenBucket {
+            capacity,
+            refill_per_second,
+        } => (
+            &TOKEN_BUCKET_SCRIPT,
+            vec![capacity.to_string(), refill_per_second.to_string()],
+        ),
+    };
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+    let res = script.eval::<Vec<i64>, _, _>(&redis.pool, key, args).await;
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_redis_command("evalsha", start, &res);
+    let res = res?;
+    if res.len() != 3 {
+        return Err(anyhow::anyhow!("unexpected rate limit result: {:?}", res));
+    }
+    Ok(RateLimitDecision {
+        allowed: res[0] == 1,
+        remaining: res[1].max(0) as u64,
+        retry_after: Duration::from_millis(res[2].max(0) as u64),
+    })
+}
+
+// 超过该数量时清理已过期的计数，避免内存持续增长
+const MEMORY_CLEANUP_SIZE: usize = 10000;
+
+static MEMORY_LIMITER: OnceLock<MemoryLimiter> = OnceLock::<MemoryLimiter>::new();
+
+#[derive(Default)]
+struct MemoryLimiter {
+    windows: DashMap<String, (u64, VecDeque<u64>)>, // (窗口毫秒数, 请求时间)
+    buckets: DashMap<String, (f64, u64, u64)>,      // (令牌数, 更新时间, 补满所需毫秒数)
+}
+
+impl MemoryLimiter {
+    fn check(&self, key: &str, algorithm: &RateLimitAlgorithm, now: u64) -> RateLimitDecision {
+        match algorithm {
+            RateLimitAlgorithm::SlidingWindow { limit, window } => {
+                if self.windows.len() > MEMORY_CLEANUP_SIZE {
+                    self.windows.retain(|_, (window, requests)| {
+                        requests.back().is_some_and(|last| last + *window > now)
+                    });
+                }
+                let window = window.as_millis() as u64;
+                let mut entry = self
+                    .windows
+                    .entry(key.to_string())
+                    .or_insert_with(|| (window, VecDeque::new()));
+                let requests = &mut entry.1;
+                while requests.front().is_some_and(|first| *first + window <= now) {
+                    requests.pop_front();
+                }
+                let count = requests.len() as u64;
+                if count < *limit {
+                    requests.push_back(now);
+                    RateLimitDecision {
+                        allowed: true,
+                        remaining: limit - count - 1,
+                        retry_after: Duration::ZERO,
+                    }
+                } else {
+                    let retry = requests
+                        .front()
+                        .map(|first| first + window - now)
+                        .unwrap_or(window);
+                    RateLimitDecision {
+                        allowed: false,
+                        remaining: 0,
+                        retry_after: Duration::from_millis(retry),
+                    }
+                }
+            }
+            RateLimitAlgorithm::TokenBucket {
+                capacity,
+                refill_per_second,
+            } => {
+                if self.buckets.len() > MEMORY_CLEANUP_SIZE {
+                    self.buckets
+                        .retain(|_, (_, ts, full_after)| *ts + *full_after > now);
+                }
+                let capacity = *capacity as f64;
+                let rate = refill_per_second / 1000.0;
+                let full_after = (capacity / rate).ceil() as u64;
+                let mut entry = self
+                    .buckets
+                    .entry(key.to_string())
+                    .or_insert((capacity, now, full_after));
+                let (tokens, ts, _) = &mut *entry;
+                *tokens = capacity.min(*tokens + now.saturating_sub(*ts) as f64 * rate);
+                *ts = now;
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    RateLimitDecision {
+                        allowed: true,
+                        remaining: *tokens as u64,
+                        retry_after: Duration::ZERO,
+                    }
+                } else {
+                    RateLimitDecision {
+                        allowed: false,
+                        remaining: 0,
+                        retry_after: Duration::from_millis(((1.0 - *tokens) / rate).ceil() as u64),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 限流中间件，优先使用PermissionMap::ratelimit中与请求路由匹配的规则，没有时使用默认规则，
+/// 超出限制时返回429和Retry-After
+/// - `App::new().wrap(RateLimiter::new().with_default(RateLimit::token_bucket(RateLimitKey::User, 20, 10.0)?))`
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    pub default: Option<RateLimit>,
+    pub prefix: String,      // redis key的前缀
+    pub trusted_proxy: bool, // 是否信任Forwarded/X-Forwarded-For中的客户端IP
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            default: None,
+            prefix: "ratelimit".into(),
+            trusted_proxy: false,
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_default(mut self, limit: RateLimit) -> Self {
+        self.default = Some(limit);
+        self
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// 服务只能经由反向代理访问时开启，按代理设置的Forwarded/X-Forwarded-For限制IP，
+    /// 否则客户端可以通过修改这些header绕过IP限流
+    pub fn with_trusted_proxy(mut self, trusted_proxy: bool) -> Self {
+        self.trusted_proxy = trusted_proxy;
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimiterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimiterMiddleware {
+            service: Rc::new(service),
+            limiter: Rc::new(self.clone()),
+        }))
+    }
+}
+
+pub struct RateLimiterMiddleware<S> {
+    service: Rc<S>,
+    limiter: Rc<RateLimiter>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimiterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let limiter = self.limiter.clone();
+        Box::pin(async move {
+            let reqpath = format!("{} {}", req.method(), req.path());
+            let rule = try_get_pmap()
+                .and_then(|pmap| pmap.find_rate_limit(&reqpath))
+                .or_else(|| limiter.default.clone().map(|limit| ("default", limit)));
+            if let Some((route, limit)) = rule {
+                let subject = limit
+                    .key
+                    .subject(req.request(), limiter.trusted_proxy)
+                    .await;
+                let key = format!("{}:{}:{}", limiter.prefix, route, subject);
+                let decision = check_rate_limit(&key, &limit.algorithm).await;
+                if !decision.allowed {
+                    tracing::warn!("rate limited: {} {}", reqpath, subject);
+                    let retry_after = decision.retry_after.as_millis().div_ceil(1000).max(1);
+                    let mut rsp = Response::<NoneBodyData>::error(
+                        StatusCode::TOO_MANY_REQUESTS,
+                        "请求过于频繁，请稍后再试",
+                    )
+                    .with_error_code("RATE_LIMITED")
+                    .with_details(serde_json::json!({ "retry_after": retry_after }))
+                    .finished_with_status();
+                    rsp.headers_mut()
+                        .insert(RETRY_AFTER, HeaderValue::from(retry_after as u64));
+                    return Ok(req.into_response(rsp).map_into_right_body());
+                }
+            }
+            service.call(req).await.map(|res| res.map_into_left_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod ratelimit_tests {
+    use super::*;
+
+    #[test]
+    fn memory_limiter_test() {
+        let limiter = MemoryLimiter::default();
+        let window = RateLimitAlgorithm::SlidingWindow {
+            limit: 2,
+            window: Duration::from_secs(1),
+        };
+        assert!(limiter.check("w", &window, 0).allowed);
+        assert!(limiter.check("w", &window, 100).allowed);
+        let decision = limiter.check("w", &window, 200);
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, Duration::from_millis(800));
+        assert!(limiter.check("w", &window, 1000).allowed);
+
+        let bucket = RateLimitAlgorithm::TokenBucket {
+            capacity: 1,
+            refill_per_second: 2.0,
+        };
+        assert!(limiter.check("b", &bucket, 0).allowed);
+        let decision = limiter.check("b", &bucket, 100);
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, Duration::from_millis(400));
+        assert!(limiter.check("b", &bucket, 500).allowed);
+        assert!(RateLimit::token_bucket(RateLimitKey::User, 10, 0.0).is_err());
+        assert!(RateLimit::token_bucket(RateLimitKey::User, 10, f64::NAN).is_err());
+    }
+
+    #[actix_web::test]
+    async fn invalid_algorithm_test() {
+        // 直接构造的无效规则不进入redis脚本和内存计数
+        let bucket = RateLimitAlgorithm::TokenBucket {
+            capacity: 1,
+            refill_per_second: 0.0,
+        };
+        assert!(bucket.validate().is_err());
+        for _ in 0..3 {
+            assert!(check_rate_limit("invalid_bucket", &bucket).await.allowed);
+        }
+        let window = RateLimitAlgorithm::SlidingWindow {
+            limit: 1,
+            window: Duration::from_micros(10),
+        };
+        assert!(window.validate().is_err());
+        assert!(check_rate_limit("invalid_window", &window).await.allowed);
+        assert!(MEMORY_LIMITER
+            .get()
+            .is_none_or(|limiter| !limiter.buckets.contains_key("invalid_bucket")));
+    }
+
+    #[actix_web::test]
+    async fn ip_subject_test() {
+        let req = actix_web::test::TestRequest::default()
+            .peer_addr("10.0.0.1:5000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "1.2.3.4"))
+            .to_http_request();
+        assert_eq!(RateLimitKey::Ip.subject(&req, false).await, "ip:10.0.0.1");
+        assert_eq!(RateLimitKey::Ip.subject(&req, true).await, "ip:1.2.3.4");
+    }
+}
@@ -0,0 +1,228 @@
+use crate::permission::try_get_pmap;
+use crate::response::EnvelopeCode;
+use actix_http::header::{HeaderName, HeaderValue};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{Error, HttpMessage};
+use futures_util::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+use std::time::Instant;
+use tracing::{field::Empty, Instrument};
+
+pub const X_REQUEST_ID: &str = "x-request-id";
+pub const TRACEPARENT: &str = "traceparent";
+
+// 客户端传入的X-Request-Id的最大长度
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// 当前请求的X-Request-Id，只在RequestLog中间件处理的请求中有值
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// 客户端传入的X-Request-Id会写入日志和响应头，只接受有限长度的`[A-Za-z0-9._-]`
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'.' | b'_' | b'-'))
+}
+
+/// 保存在请求extensions中的请求id，handler中可通过`req.extensions().get::<RequestId>()`获取
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+/// W3C traceparent: `00-{trace_id}-{parent_id}-{flags}`
+#[derive(Clone, Debug)]
+pub struct TraceParent {
+    pub trace_id: String,
+    pub parent_id: Option<String>,
+    pub span_id: String,
+    pub flags: String,
+}
+
+impl TraceParent {
+    /// 解析请求中的traceparent，格式不对或没有时开始新的trace
+    pub fn from_header(header: Option<&str>) -> Self {
+        let span_id = new_span_id();
+        if let Some(header) = header {
+            let parts: Vec<&str> = header.trim().split('-').collect();
+            if parts.len() == 4
+                && parts[0].len() == 2
+                && parts[1].len() == 32
+                && parts[2].len() == 16
+                && parts[1..3]
+                    .iter()
+                    .all(|each| each.chars().all(|c| c.is_ascii_hexdigit()))
+            {
+                return Self {
+                    trace_id: parts[1].to_lowercase(),
+                    parent_id: Some(parts[2].to_lowercase()),
+                    span_id,
+                    flags: parts[3].to_string(),
+                };
+            }
+        }
+        Self {
+            trace_id: uuid::Uuid::new_v4().simple().to_string(),
+            parent_id: None,
+            span_id,
+            flags: "01".into(),
+        }
+    }
+
+    /// 向下游和返回中传递的traceparent，parent_id为当前请求的span_id
+    pub fn to_header(&self) -> String {
+        format!("00-{}-{}-{}", self.trace_id, self.span_id, self.flags)
+    }
+}
+
+fn new_span_id() -> String {
+    let mut id = uuid::Uuid::new_v4().simple().to_string();
+    id.truncate(16);
+    id
+}
+
+/// 请求日志和tracing中间件，每个请求一个span，记录method、path、匹配的路由、用户账号、
+/// Response中的code和耗时，并传递或生成X-Request-Id和traceparent
+/// - `App::new().wrap(RequestLog)`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RequestLog;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestLogMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestLogMiddleware { service }))
+    }
+}
+
+pub struct RequestLogMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let request_id = req
+            .headers()
+            .get(X_REQUEST_ID)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| is_valid_request_id(value))
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+        let traceparent = TraceParent::from_header(
+            req.headers()
+                .get(TRACEPARENT)
+                .and_then(|value| value.to_str().ok()),
+        );
+        req.extensions_mut().insert(RequestId(request_id.clone()));
+        req.extensions_mut().insert(traceparent.clone());
+
+        let span = tracing::info_span!(
+            "request",
+            method = %req.method(),
+            path = %req.path(),
+            route = req.match_pattern().unwrap_or_default(),
+            request_id = request_id.as_str(),
+            trace_id = traceparent.trace_id.as_str(),
+            user = Empty,
+            status = Empty,
+            code = Empty,
+            latency_ms = Empty,
+        );
+
+        let fut = {
+            let _enter = span.enter();
+            REQUEST_ID.sync_scope(request_id.clone(), || self.service.call(req))
+        };
+        let request_id_header = request_id.clone();
+        Box::pin(
+            REQUEST_ID
+                .scope(request_id, async move {
+                    let span = tracing::Span::current();
+                    match fut.await {
+                        Ok(mut res) => {
+                            if let Some(pmap) = try_get_pmap() {
+                                if let Ok(access) = pmap.decode_request_token(res.request()).await {
+                                    span.record("user", access.user_account.as_str());
+                                }
+                            }
+                            let status = res.status().as_u16();
+                            let code = res
+                                .response()
+                                .extensions()
+                                .get::<EnvelopeCode>()
+                                .map(|code| code.0)
+                                .unwrap_or(status);
+                            let latency = start.elapsed().as_millis() as u64;
+                            span.record("status", status);
+                            span.record("code", code);
+                            span.record("latency_ms", latency);
+                            if code >= 500 {
+                                tracing::error!("request finished");
+                            } else if code >= 400 {
+                                tracing::warn!("request finished");
+                            } else {
+                                tracing::info!("request finished");
+                            }
+
+                            let headers = res.headers_mut();
+                            if let Ok(value) = HeaderValue::from_str(&request_id_header) {
+                                headers.insert(HeaderName::from_static(X_REQUEST_ID), value);
+                            }
+                            if let Ok(value) = HeaderValue::from_str(&traceparent.to_header()) {
+                                headers.insert(HeaderName::from_static(TRACEPARENT), value);
+                            }
+                            Ok(res)
+                        }
+                        Err(e) => {
+                            span.record("latency_ms", start.elapsed().as_millis() as u64);
+                            tracing::error!("request failed: {:?}", e);
+                            Err(e)
+                        }
+                    }
+                })
+                .instrument(span),
+        )
+    }
+}
+
+#[cfg(test)]
+mod request_log_tests {
+    use super::*;
+
+    #[test]
+    fn request_id_test() {
+        assert!(is_valid_request_id("4bf92f3577b34da6-a3ce929d0e0e4736"));
+        assert!(is_valid_request_id("req_1.2"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("id\r\nx: y"));
+        assert!(!is_valid_request_id("请求"));
+        assert!(!is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::db::DbConfig;
+use crate::lock::DistributedLock;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement, TransactionTrait};
+use sea_orm_migration::{MigrationTrait, MigratorTrait};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::info;
+
+pub use sea_orm_migration;
+
+/// 创建迁移的函数，如`|| Box::new(m20240101_000001_create_user::Migration)`
+pub type MigrationFactory = fn() -> Box<dyn MigrationTrait>;
+
+static MIGRATIONS: Mutex<Vec<MigrationFactory>> = Mutex::new(Vec::new());
+
+/// 注册迁移，需要在run_migrations或cli_main之前调用，迁移按名字排序执行
+pub fn register_migration(factory: MigrationFactory) {
+    MIGRATIONS.lock().unwrap().push(factory);
+}
+
+/// 已注册的迁移，用于调用sea-orm-migration的MigratorTrait
+pub struct Migrator;
+
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        let mut migrations: Vec<Box<dyn MigrationTrait>> = MIGRATIONS
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|factory| factory())
+            .collect();
+        migrations.sort_by(|a, b| a.name().cmp(b.name()));
+        migrations
+    }
+}
+
+const LOCK_NAME: &str = "webbase_migrate";
+// pg_advisory_lock的key，同一数据库的所有副本使用相同的值
+const PG_LOCK_KEY: i64 = 0x5ebb_a5e0;
+
+/// 多副本同时启动时保证只有一个副本执行迁移，其余副本等待迁移完成
+pub enum MigrationLock {
+    /// mysql的GET_LOCK或postgres的pg_advisory_lock，sqlite不加锁
+    Database(Duration),
+    /// redis分布式锁，等待时间由DistributedLock::with_retry设置
+    Redis(DistributedLock),
+    /// 只有一个副本时不加锁
+    None,
+}
+
+impl Default for MigrationLock {
+    fn default() -> Self {
+        Self::Database(Duration::from_secs(600))
+    }
+}
+
+/// 执行未执行的迁移，没有待执行的迁移时不加锁，加锁后由Migrator重新检查
+/// ```ignore
+/// migrate::register_migration(|| Box::new(m20240101_000001_create_user::Migration));
+/// init_mysql(dburl);
+/// migrate::run_migrations(get_mysql(), MigrationLock::default()).await?;
+/// ```
+pub async fn run_migrations(db: &DatabaseConnection, lock: MigrationLock) -> anyhow::Result<()> {
+    let pending = Migrator::get_pending_migrations(db).await?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+    info!("{} pending migrations", pending.len());
+    match lock {
+        MigrationLock::Database(timeout) => with_database_lock(db, timeout).await,
+        MigrationLock::Redis(lock) => {
+            // 迁移可能执行较长时间，由watchdog续期
+            lock.with_lock(LOCK_NAME, Duration::from_secs(30), |_| async {
+                Ok(Migrator::up(db, None).await?)
+            })
+            .await
+        }
+        MigrationLock::None => Ok(Migrator::up(db, None).await?),
+    }
+}
+
+/// 对init_mysql初始化的连接执行迁移，使用mysql的GET_LOCK
+pub async fn migrate_mysql() -> anyhow::Result<()> {
+    run_migrations(crate::get_mysql(), MigrationLock::default()).await
+}
+
+/// GET_LOCK和pg_advisory_lock属于会话，在事务中持有同一个连接，迁移使用连接池的其它连接
+async fn with_database_lock(db: &DatabaseConnection, timeout: Duration) -> anyhow::Result<()> {
+    let backend = db.get_database_backend();
+    let (lock_sql, unlock_sql) = match backend {
+        DbBackend::MySql => (
+            Statement::from_sql_and_values(
+                backend,
+                "SELECT GET_LOCK(?, ?) AS locked",
+                [LOCK_NAME.into(), (timeout.as_secs() as i64).into()],
+            ),
+            Statement::from_sql_and_values(backend, "SELECT RELEASE_LOCK(?)", [LOCK_NAME.into()]),
+        ),
+        DbBackend::Postgres => (
+            Statement::from_sql_and_values(
+                backend,
+                "SELECT pg_advisory_lock($1)",
+                [PG_LOCK_KEY.into()],
+            ),
+            Statement::from_sql_and_values(
+                backend,
+                "SELECT pg_advisory_unlock($1)",
+                [PG_LOCK_KEY.into()],
+            ),
+        ),
+        DbBackend::Sqlite => return Ok(Migrator::up(db, None).await?),
+    };
+
+    let txn = db.begin().await?;
+    if backend == DbBackend::Postgres {
+        // pg_advisory_lock没有超时参数，使用lock_timeout限制等待时间，
+        // SET LOCAL只在当前事务内生效，不影响连接归还连接池后的其他查询
+        txn.execute_unprepared(&format!(
+            "SET LOCAL lock_timeout = '{}s'",
+            timeout.as_secs()
+        ))
+        .await?;
+    }
+    let row = txn.query_one(lock_sql).await?;
+    if backend == DbBackend::MySql {
+        let locked: Option<i64> = row
+            .map(|row| row.try_get("", "locked"))
+            .transpose()?
+            .flatten();
+        if locked != Some(1) {
+            return Err(anyhow::anyhow!("获取迁移锁{}超时", LOCK_NAME));
+        }
+    }
+    info!("migration lock acquired");
+    let res = Migrator::up(db, None).await;
+    if let Err(e) = txn.query_one(unlock_sql).await {
+        tracing::warn!("release migration lock error: {:?}", e);
+    }
+    txn.commit().await?;
+    Ok(res?)
+}
+
+/// 迁移命令行，服务注册迁移后在自己的bin中调用，数据库url来自DATABASE_URL环境变量，
+/// 完整的示例见examples/migrate.rs
+/// - `migrate up [n]`: 执行未执行的迁移，默认全部
+/// - `migrate down [n]`: 回滚最近的迁移，默认1个
+/// - `migrate status`: 各迁移的执行状态
+/// - `migrate fresh`: 删除所有表后重新执行全部迁移
+/// ```ignore
+/// // src/bin/migrate.rs
+/// #[tokio::main]
+/// async fn main() -> anyhow::Result<()> {
+///     migration::register_all(); // 调用migrate::register_migration注册服务的迁移
+///     webbase::migrate::cli_main().await
+/// }
+/// ```
+pub async fn cli_main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.first().map(String::as_str).unwrap_or("status");
+    let steps = args
+        .get(1)
+        .map(|steps| steps.parse::<u32>())
+        .transpose()
+        .map_err(|_| anyhow::anyhow!("无效的迁移数量-{}", args[1]))?;
+    let url = std::env::var("DATABASE_URL").map_err(|_| anyhow::anyhow!("未设置DATABASE_URL"))?;
+    if Migrator::migrations().is_empty() {
+        return Err(anyhow::anyhow!(
+            "没有注册迁移，需要先调用register_migration"
+        ));
+    }
+    let db = DbConfig::new(&url).with_connections(1, 2).connect().await?;
+    match command {
+        "up" => Migrator::up(&db, steps).await?,
+        "down" => Migrator::down(&db, Some(steps.unwrap_or(1))).await?,
+        "status" => {
+            for migration in Migrator::get_migration_with_status(&db).await? {
+                println!("{}\t{}", migration.name(), migration.status());
+            }
+        }
+        "fresh" => Migrator::fresh(&db).await?,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "未知的命令-{}，可用的命令: up [n], down [n], status, fresh",
+                command
+            ))
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod migrate_tests {
+    use super::*;
+    use sea_orm_migration::prelude::*;
+
+    #[derive(DeriveMigrationName)]
+    struct Migration;
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .get_connection()
+                .execute_unprepared("CREATE TABLE migrate_test (id INTEGER PRIMARY KEY)")
+                .await?;
+            Ok(())
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .get_connection()
+                .execute_unprepared("DROP TABLE migrate_test")
+                .await?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn sqlite_migrate_test() {
+        register_migration(|| Box::new(Migration));
+        let db = DbConfig::new("sqlite::memory:").connect().await.unwrap();
+        run_migrations(&db, MigrationLock::default()).await.unwrap();
+        assert!(Migrator::get_pending_migrations(&db)
+            .await
+            .unwrap()
+            .is_empty());
+        db.execute_unprepared("INSERT INTO migrate_test (id) VALUES (1)")
+            .await
+            .unwrap();
+        Migrator::down(&db, None).await.unwrap();
+        assert_eq!(
+            Migrator::get_pending_migrations(&db).await.unwrap().len(),
+            1
+        );
+    }
+}
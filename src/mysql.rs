@@ -1,21 +1,21 @@
+use crate::db::{register_database, DbConfig, PRIMARY};
 use sea_orm::DatabaseConnection;
-use sea_orm::{ConnectOptions, Database};
 use std::sync::OnceLock;
 use tracing::info;
 
 static MYSQL: OnceLock<DatabaseConnection> = OnceLock::<DatabaseConnection>::new();
 
+/// 使用DbConfig的默认连接池配置
 pub fn init_mysql(dburl: String) -> &'static DatabaseConnection {
+    init_mysql_with_config(DbConfig::new(&dburl))
+}
+
+/// 连接后同时以db::PRIMARY注册到数据库注册表
+pub fn init_mysql_with_config(config: DbConfig) -> &'static DatabaseConnection {
     MYSQL.get_or_init(|| {
-        let mut opt = ConnectOptions::new(dburl.to_owned());
-        opt.max_connections(100)
-            .min_connections(5)
-            .connect_timeout(std::time::Duration::from_secs(8))
-            .idle_timeout(std::time::Duration::from_secs(8))
-            .max_lifetime(std::time::Duration::from_secs(8))
-            .sqlx_logging(false);
-        let conn = futures::executor::block_on(async { Database::connect(opt).await.unwrap() });
+        let conn = futures::executor::block_on(async { config.connect().await.unwrap() });
         info!("connect to mysql successfully");
+        register_database(PRIMARY, conn.clone());
         conn
     })
 }
@@ -23,3 +23,7 @@ pub fn init_mysql(dburl: String) -> &'static DatabaseConnection {
 pub fn get_mysql() -> &'static DatabaseConnection {
     MYSQL.get().unwrap()
 }
+
+pub fn try_get_mysql() -> Option<&'static DatabaseConnection> {
+    MYSQL.get()
+}
@@ -0,0 +1,206 @@
+use crate::permission::PermissionMap;
+use actix_web::{web, HttpResponse, Scope};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme};
+use utoipa::openapi::{ComponentsBuilder, OpenApi};
+
+pub const BEARER_AUTH: &str = "bearer_auth";
+
+/// PathItem中存在的(方法, Operation)，`path_operations!(item, &mut)`得到可修改的Operation
+macro_rules! path_operations {
+    ($item:ident, $($borrow:tt)+) => {
+        [
+            ("GET", $($borrow)+ $item.get),
+            ("PUT", $($borrow)+ $item.put),
+            ("POST", $($borrow)+ $item.post),
+            ("DELETE", $($borrow)+ $item.delete),
+            ("OPTIONS", $($borrow)+ $item.options),
+            ("HEAD", $($borrow)+ $item.head),
+            ("PATCH", $($borrow)+ $item.patch),
+            ("TRACE", $($borrow)+ $item.trace),
+        ]
+        .into_iter()
+        .filter_map(|(method, operation)| operation.into_iter().next().map(|each| (method, each)))
+    };
+}
+
+/// 注册Bearer JWT鉴权方式，除PermissionMap白名单外的接口都标记为需要鉴权，
+/// pmap中配置了权限的接口在x-permission扩展字段中给出(page, action)
+pub fn apply_security(openapi: &mut OpenApi, pmap: &PermissionMap) {
+    let components = openapi
+        .components
+        .get_or_insert_with(|| ComponentsBuilder::new().build());
+    components.add_security_scheme(
+        BEARER_AUTH,
+        SecurityScheme::Http(
+            HttpBuilder::new()
+                .scheme(HttpAuthScheme::Bearer)
+                .bearer_format("JWT")
+                .build(),
+        ),
+    );
+
+    for (path, item) in openapi.paths.paths.iter_mut() {
+        for (method, operation) in path_operations!(item, &mut) {
+            let route = format!("{} {}", method, path);
+            if pmap.is_whitelisted(&route) {
+                // 空的SecurityRequirement表示该接口不需要鉴权
+                operation.security = Some(vec![SecurityRequirement::default()]);
+                continue;
+            }
+            operation.security = Some(vec![SecurityRequirement::new::<_, _, &str>(
+                BEARER_AUTH,
+                [],
+            )]);
+            if let Some((page, action)) = pmap.find_permission(&route) {
+                let extensions = operation.extensions.get_or_insert_with(Default::default);
+                extensions.insert(
+                    "x-permission".into(),
+                    serde_json::json!({ "page": page, "action": action }),
+                );
+            }
+        }
+    }
+}
+
+/// OpenAPI文档中的所有路由，格式为"GET /api/order/{id}"，可用于PermissionMap::check_routes
+pub fn openapi_routes(openapi: &OpenApi) -> Vec<String> {
+    let mut routes = Vec::new();
+    for (path, item) in openapi.paths.paths.iter() {
+        for (method, _) in path_operations!(item, &) {
+            routes.push(format!("{} {}", method, path));
+        }
+    }
+    routes
+}
+
+/// 提供OpenAPI文档和浏览页面的scope，页面的静态资源从CDN加载
+/// - `{path}/openapi.json`: OpenAPI文档
+/// - `{path}/swagger`: Swagger UI
+/// - `{path}/rapidoc`: RapiDoc
+pub fn docs_scope(path: &str, openapi: OpenApi) -> Scope {
+    let spec_url = format!("{}/openapi.json", path.trim_end_matches('/'));
+    let spec = openapi.to_json().unwrap_or_default();
+    let swagger = SWAGGER_HTML.replace("{spec_url}", &spec_url);
+    let rapidoc = RAPIDOC_HTML.replace("{spec_url}", &spec_url);
+    web::scope(path)
+        .route(
+            "/openapi.json",
+            web::get().to(move || {
+                let spec = spec.clone();
+                async move {
+                    HttpResponse::Ok()
+                        .content_type("application/json")
+                        .body(spec)
+                }
+            }),
+        )
+        .route(
+            "/swagger",
+            web::get().to(move || {
+                let swagger = swagger.clone();
+                async move { HttpResponse::Ok().content_type("text/html").body(swagger) }
+            }),
+        )
+        .route(
+            "/rapidoc",
+            web::get().to(move || {
+                let rapidoc = rapidoc.clone();
+                async move { HttpResponse::Ok().content_type("text/html").body(rapidoc) }
+            }),
+        )
+}
+
+const SWAGGER_HTML: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8" />
+<title>Swagger UI</title>
+<link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+<div id="swagger-ui"></div>
+<script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+<script>
+window.ui = SwaggerUIBundle({ url: "{spec_url}", dom_id: "#swagger-ui" });
+</script>
+</body>
+</html>
+"##;
+
+const RAPIDOC_HTML: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8" />
+<title>RapiDoc</title>
+<script type="module" src="https://unpkg.com/rapidoc/dist/rapidoc-min.js"></script>
+</head>
+<body>
+<rapi-doc spec-url="{spec_url}" render-style="read" allow-authentication="true"></rapi-doc>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod openapi_tests {
+    use super::*;
+    use std::collections::HashMap;
+    use utoipa::openapi::path::{HttpMethod, OperationBuilder, PathItem, PathsBuilder};
+    use utoipa::openapi::OpenApiBuilder;
+
+    #[test]
+    fn apply_security_test() {
+        let mut openapi = OpenApiBuilder::new()
+            .paths(
+                PathsBuilder::new()
+                    .path(
+                        "/api/v1/order/{id}",
+                        PathItem::from_http_methods(
+                            [HttpMethod::Get, HttpMethod::Delete],
+                            OperationBuilder::new(),
+                        ),
+                    )
+                    .path(
+                        "/api/v1/login",
+                        PathItem::new(HttpMethod::Post, OperationBuilder::new()),
+                    ),
+            )
+            .build();
+        let pmap = PermissionMap {
+            pmap: HashMap::from([(r"^DELETE /api/v1/order/", ("order", "delete"))]),
+            whitelist: vec!["POST /api/v1/login"],
+            secret: String::new(),
+            use_rsa: false,
+            app_name: "test".into(),
+            use_local: true,
+            config: Default::default(),
+            ratelimit: Default::default(),
+        };
+        apply_security(&mut openapi, &pmap);
+
+        let mut routes = openapi_routes(&openapi);
+        routes.sort();
+        assert_eq!(
+            routes,
+            vec![
+                "DELETE /api/v1/order/{id}",
+                "GET /api/v1/order/{id}",
+                "POST /api/v1/login"
+            ]
+        );
+        let order = openapi.paths.get_path_item("/api/v1/order/{id}").unwrap();
+        let delete = order.delete.as_ref().unwrap();
+        assert_eq!(
+            delete.extensions.as_ref().unwrap().get("x-permission"),
+            Some(&serde_json::json!({ "page": "order", "action": "delete" }))
+        );
+        let bearer = SecurityRequirement::new::<_, _, &str>(BEARER_AUTH, []);
+        assert!(delete.security == Some(vec![bearer.clone()]));
+        let get = order.get.as_ref().unwrap();
+        assert!(get.security == Some(vec![bearer]));
+        assert!(get.extensions.is_none());
+        let login = openapi.paths.get_path_item("/api/v1/login").unwrap();
+        assert!(
+            login.post.as_ref().unwrap().security == Some(vec![SecurityRequirement::default()])
+        );
+    }
+}
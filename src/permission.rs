@@ -1,3 +1,4 @@
+use crate::middleware::ratelimit::RateLimit;
 use crate::token::AccessToken;
 use crate::token_rsa::AccessTokenRsa;
 use crate::{idmlogin::get_idm, rbac::get_rbac};
@@ -9,7 +10,7 @@ use anyhow::anyhow;
 use dashmap::DashMap;
 use regex::Regex;
 use serde::Serialize;
-use std::sync::OnceLock;
+use std::sync::{Mutex, OnceLock};
 use std::{collections::HashMap, fmt::Debug};
 
 #[derive(Default)]
@@ -21,26 +22,75 @@ pub struct PermissionMap {
     pub app_name: String, // 当前业务的名字
     pub use_local: bool,  // 使用本地端的RBAC配置，不优先使用IDM服务器的
     pub config: DashMap<String, String>, // 存放系统相关的一些配置信息，业务自行决定使用
+    pub ratelimit: DashMap<&'static str, RateLimit>, // 按路由配置的限流规则，路由格式与pmap相同
 }
 
+/// #[permission(...)]宏注册的路由权限，whitelist为true时加入白名单，page和action为空
+#[derive(Debug)]
+pub struct PermissionRoute {
+    pub route: &'static str,
+    pub page: &'static str,
+    pub action: &'static str,
+    pub whitelist: bool,
+}
+
+inventory::collect!(PermissionRoute);
+
 static PMAP: OnceLock<PermissionMap> = OnceLock::<PermissionMap>::new();
 
+static RUNTIME_ROUTES: Mutex<Vec<PermissionRoute>> = Mutex::new(Vec::new());
+
+/// 运行时注册路由权限，如CrudResource生成的路由，需要在init_pmap之前调用才会合并到pmap中
+pub fn register_permission_route(route: PermissionRoute) {
+    if let Some(pmap) = PMAP.get() {
+        if !pmap.pmap.contains_key(route.route) && !pmap.whitelist.contains(&route.route) {
+            tracing::warn!(
+                "permission route registered after init_pmap is ignored: {}",
+                route.route
+            );
+        }
+        return;
+    }
+    let mut routes = RUNTIME_ROUTES.lock().unwrap();
+    if !routes.iter().any(|each| each.route == route.route) {
+        routes.push(route);
+    }
+}
+
+/// 除参数给出的pmap和whitelist外，还会合并#[permission(...)]宏和register_permission_route注册的路由，
+/// 参数中已有的路由优先
 pub fn init_pmap(
-    whitelist: Vec<&'static str>,
-    pmap: HashMap<&'static str, (&'static str, &'static str)>,
+    mut whitelist: Vec<&'static str>,
+    mut pmap: HashMap<&'static str, (&'static str, &'static str)>,
     secret: String,
     app_name: String,
     use_local: bool,
     use_rsa: bool,
 ) -> &'static PermissionMap {
-    PMAP.get_or_init(|| PermissionMap {
-        pmap: pmap,
-        whitelist: whitelist,
-        secret: secret,
-        app_name: app_name,
-        use_local: use_local,
-        use_rsa: use_rsa,
-        config: DashMap::default(),
+    PMAP.get_or_init(|| {
+        let runtime_routes = std::mem::take(&mut *RUNTIME_ROUTES.lock().unwrap());
+        let mut routes: Vec<&PermissionRoute> =
+            inventory::iter::<PermissionRoute>.into_iter().collect();
+        routes.extend(runtime_routes.iter());
+        for each in routes {
+            if each.whitelist {
+                if !whitelist.contains(&each.route) {
+                    whitelist.push(each.route);
+                }
+            } else {
+                pmap.entry(each.route).or_insert((each.page, each.action));
+            }
+        }
+        PermissionMap {
+            pmap,
+            whitelist,
+            secret,
+            app_name,
+            use_local,
+            use_rsa,
+            config: DashMap::default(),
+            ratelimit: DashMap::default(),
+        }
     })
 }
 
@@ -48,7 +98,76 @@ pub fn get_pmap() -> &'static PermissionMap {
     PMAP.get().unwrap()
 }
 
+pub fn try_get_pmap() -> Option<&'static PermissionMap> {
+    PMAP.get()
+}
+
 impl PermissionMap {
+    /// 找出既没有权限配置也不在白名单中的路由，路由格式为"GET /api/order/{id}"，
+    /// 路径参数替换为"0"后再与pmap和whitelist匹配
+    pub fn find_unprotected_routes<I, S>(&self, routes: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let param = Regex::new(r"\{[^}]*\}").unwrap();
+        routes
+            .into_iter()
+            .filter(|route| {
+                let reqpath = param.replace_all(route.as_ref(), "0");
+                !route_in_list(self.whitelist.iter(), &reqpath)
+                    && !route_in_list(self.pmap.keys(), &reqpath)
+            })
+            .map(|route| route.as_ref().to_string())
+            .collect()
+    }
+
+    /// 查找请求路由对应的限流规则，先按字符串完全匹配，再按正则表达式匹配
+    pub fn find_rate_limit(&self, reqpath: &str) -> Option<(&'static str, RateLimit)> {
+        if let Some(each) = self.ratelimit.get(reqpath) {
+            return Some((*each.key(), each.value().clone()));
+        }
+        self.ratelimit
+            .iter()
+            .find(|each| match Regex::new(each.key()) {
+                Ok(re) => re.is_match(reqpath),
+                Err(_) => false,
+            })
+            .map(|each| (*each.key(), each.value().clone()))
+    }
+
+    /// 是否在白名单中，先按字符串完全匹配，再按正则表达式匹配
+    pub fn is_whitelisted(&self, reqpath: &str) -> bool {
+        route_in_list(self.whitelist.iter(), reqpath)
+    }
+
+    /// 查找请求路由需要的(page, action)，先按字符串完全匹配，再按正则表达式匹配
+    pub fn find_permission(&self, reqpath: &str) -> Option<(&'static str, &'static str)> {
+        if let Some(permission) = self.pmap.get(reqpath) {
+            return Some(*permission);
+        }
+        self.pmap
+            .iter()
+            .find(|(each, _)| match Regex::new(each) {
+                Ok(re) => re.is_match(reqpath),
+                Err(_) => false,
+            })
+            .map(|(_, permission)| *permission)
+    }
+
+    /// 启动时检查路由，输出没有任何权限配置的路由
+    pub fn check_routes<I, S>(&self, routes: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let unprotected = self.find_unprotected_routes(routes);
+        for each in unprotected.iter() {
+            tracing::warn!("route without permission or whitelist: {}", each);
+        }
+        unprotected
+    }
+
     pub async fn get_rbac_config(&self) -> RpConfig {
         if self.use_local {
             return get_rbac().rbac.read().await.clone();
@@ -71,6 +190,34 @@ impl PermissionMap {
     }
 
     pub async fn check_token_only(&self, req: &HttpRequest) -> anyhow::Result<AccessToken> {
+        let result = self.decode_request_token(req).await;
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_auth_decision("token_only", &result);
+        result
+    }
+
+    pub async fn check_and_verify(&self, req: &HttpRequest) -> anyhow::Result<AccessToken> {
+        let result = self.verify_request(req).await;
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_auth_decision("verify", &result);
+        result
+    }
+
+    pub async fn check_and_verify_map(
+        &self,
+        req: &HttpRequest,
+        rbac_key: &String,
+    ) -> anyhow::Result<AccessToken> {
+        let result = self.verify_request_map(req, rbac_key).await;
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_auth_decision("verify_map", &result);
+        result
+    }
+
+    pub(crate) async fn decode_request_token(
+        &self,
+        req: &HttpRequest,
+    ) -> anyhow::Result<AccessToken> {
         let (headers, _reqpath) = get_token_and_path(req);
         if !headers.contains_key(actix_http::header::AUTHORIZATION) {
             return Err(anyhow!("Header中无鉴权信息"));
@@ -80,7 +227,7 @@ impl PermissionMap {
             .get(actix_http::header::AUTHORIZATION)
             .unwrap()
             .to_str()
-            .unwrap()
+            .map_err(|_| anyhow!("Header中鉴权信息包含非法字符"))?
             .to_string();
         // if !token.contains("Bearer ") || token.len() < 7 {
         if token.len() < 7 {
@@ -106,7 +253,7 @@ impl PermissionMap {
         Ok(access)
     }
 
-    pub async fn check_and_verify(&self, req: &HttpRequest) -> anyhow::Result<AccessToken> {
+    async fn verify_request(&self, req: &HttpRequest) -> anyhow::Result<AccessToken> {
         let (headers, reqpath) = get_token_and_path(req);
         // tracing::info!("check for {:?}", reqpath);
 
@@ -118,7 +265,7 @@ impl PermissionMap {
             .get(actix_http::header::AUTHORIZATION)
             .unwrap()
             .to_str()
-            .unwrap()
+            .map_err(|_| anyhow!("Header中鉴权信息包含非法字符"))?
             .to_string();
         // if !token.contains("Bearer ") || token.len() < 7 {
         if token.len() < 7 {
@@ -189,7 +336,7 @@ impl PermissionMap {
         return Err(anyhow!("核查所有权限后该用户无对应权限:{:?}", user_account));
     }
 
-    pub async fn check_and_verify_map(
+    async fn verify_request_map(
         &self,
         req: &HttpRequest,
         rbac_key: &String,
@@ -205,7 +352,7 @@ impl PermissionMap {
             .get(actix_http::header::AUTHORIZATION)
             .unwrap()
             .to_str()
-            .unwrap()
+            .map_err(|_| anyhow!("Header中鉴权信息包含非法字符"))?
             .to_string();
         // if !token.contains("Bearer ") || token.len() < 7 {
         if token.len() < 7 {
@@ -277,6 +424,16 @@ impl PermissionMap {
     }
 }
 
+fn route_in_list<'a>(mut list: impl Iterator<Item = &'a &'static str>, reqpath: &str) -> bool {
+    list.any(|each| {
+        each.eq(&reqpath)
+            || match Regex::new(each) {
+                Ok(re) => re.is_match(reqpath),
+                Err(_) => false,
+            }
+    })
+}
+
 pub fn get_token_and_path(req: &HttpRequest) -> (HeaderMap, String) {
     let headers = req.headers();
     let reqpath = format!("{} {}", req.method(), req.path());
@@ -1,3 +1,4 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::sync::OnceLock;
 use tokio::sync::RwLock;
@@ -8,12 +9,16 @@ static RBAC: OnceLock<RbacCache> = OnceLock::<RbacCache>::new();
 pub struct RbacCache {
     pub rbac: Arc<RwLock<RpConfig>>,
     pub rbac_map: Arc<RwLock<HashMap<String, RpConfig>>>,
+    version: AtomicU64,    // 每次更新加1
+    updated_at: AtomicU64, // 最后一次更新的时间戳，秒
 }
 
 pub fn init_rbac(rpconfig: RpConfig) -> &'static RbacCache {
     RBAC.get_or_init(|| RbacCache {
         rbac: Arc::new(RwLock::new(rpconfig)),
         rbac_map: Arc::new(RwLock::new(HashMap::default())),
+        version: AtomicU64::new(0),
+        updated_at: AtomicU64::new(chrono::Utc::now().timestamp() as u64),
     })
 }
 
@@ -21,10 +26,29 @@ pub fn get_rbac() -> &'static RbacCache {
     RBAC.get().unwrap()
 }
 
+pub fn try_get_rbac() -> Option<&'static RbacCache> {
+    RBAC.get()
+}
+
 impl RbacCache {
+    /// 返回(版本号, 最后更新时间戳)
+    pub fn version(&self) -> (u64, u64) {
+        (
+            self.version.load(Ordering::Relaxed),
+            self.updated_at.load(Ordering::Relaxed),
+        )
+    }
+
+    fn touch(&self) {
+        self.version.fetch_add(1, Ordering::Relaxed);
+        self.updated_at
+            .store(chrono::Utc::now().timestamp() as u64, Ordering::Relaxed);
+    }
+
     pub async fn update_rbac_map(&self, key: &String, rpconfig: RpConfig) {
         let mut map = self.rbac_map.write().await;
         map.insert(key.clone(), rpconfig);
+        self.touch();
     }
 
     pub async fn get_rbac_from_map(&self, rbac_key: &String) -> Option<RpConfig> {
@@ -40,6 +64,7 @@ impl RbacCache {
     pub async fn update_rbac(&self, rpconfig: RpConfig) {
         let mut rbac = self.rbac.write().await;
         *rbac = rpconfig;
+        self.touch();
     }
 }
 
@@ -1,20 +1,38 @@
+use crate::redisconfig::{new_redlock, RedisPoolConfig, RedisTlsConfig, RedisTopology};
 #[allow(unused_imports)]
 use redis::{
     aio::MultiplexedConnection, AsyncCommands, Client, ConnectionAddr, ConnectionInfo,
     RedisConnectionInfo,
 };
 use redlock::RedLock;
-use std::{str::FromStr, sync::OnceLock};
+use std::{
+    str::FromStr,
+    sync::{Mutex, OnceLock},
+};
 use tracing::info;
 
 #[derive(Clone)]
 pub struct RedisPool {
     pub client: Client,
     pub connection: MultiplexedConnection,
-    pub redlock: RedLock,
+    pub redlock: RedLock, // 同步阻塞的实现，异步代码中使用lock::DistributedLock
+}
+
+impl RedisPool {
+    /// 哨兵或集群部署没有在redlock.nodes中配置相互独立的节点时返回错误
+    pub fn redlock(&self) -> anyhow::Result<&RedLock> {
+        if self.redlock.servers.is_empty() {
+            return Err(anyhow::anyhow!(
+                "哨兵或集群部署需要在redlock.nodes中配置相互独立的redis节点"
+            ));
+        }
+        Ok(&self.redlock)
+    }
 }
 
 static REDISPOOL: OnceLock<RedisPool> = OnceLock::<RedisPool>::new();
+// 保证并发初始化时只连接一次
+static REDISPOOL_INIT: Mutex<()> = Mutex::new(());
 
 /// redis url format
 /// - `{redis|rediss}://[<username>][:<password>@]<hostname>[:port][/<db>]`
@@ -22,6 +40,7 @@ static REDISPOOL: OnceLock<RedisPool> = OnceLock::<RedisPool>::new();
 /// - redis-cli -h 49.235.147.196 -p 9015 -a kZhOcgQmRUu7DZH
 ///   - sudo apt-get install redis-tools
 pub fn init_redis_pool(redis_url: String) -> &'static RedisPool {
+    let _init = REDISPOOL_INIT.lock().unwrap_or_else(|e| e.into_inner());
     REDISPOOL.get_or_init(|| {
         // let client_info = ConnectionInfo {
         //     addr: ConnectionAddr::Tcp(redis_host.clone(), redis_port),
@@ -41,7 +60,7 @@ pub fn init_redis_pool(redis_url: String) -> &'static RedisPool {
         //     "redis://default:{}@{}:{}",
         //     redis_pass, redis_host, redis_port
         // );
-        let redlock = redlock::RedLock::new(vec![redis_url]);
+        let redlock = new_redlock(Ok(vec![redis_url]));
         info!("connect to redis successfully");
         RedisPool {
             client: client,
@@ -51,10 +70,94 @@ pub fn init_redis_pool(redis_url: String) -> &'static RedisPool {
     })
 }
 
+/// 按配置连接单机或哨兵部署的redis，哨兵部署在初始化时获取主节点地址，主从切换后需要重建连接，
+/// 集群部署请使用redisfred::init_redis_pool_with_config
+pub fn init_redis_pool_with_config(config: RedisPoolConfig) -> anyhow::Result<&'static RedisPool> {
+    let _init = REDISPOOL_INIT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pool) = REDISPOOL.get() {
+        return Ok(pool);
+    }
+    let client_info = match &config.topology {
+        RedisTopology::Centralized { node } => ConnectionInfo::from_str(&config.node_url(node))?,
+        RedisTopology::Sentinel {
+            nodes,
+            service_name,
+            sentinel_username,
+            sentinel_password,
+        } => {
+            let mut sentinel_config = config.clone().with_auth(
+                sentinel_username.clone().or(config.username.clone()),
+                sentinel_password.clone().or(config.password.clone()),
+            );
+            sentinel_config.db = None;
+            let sentinel_urls = nodes
+                .iter()
+                .map(|node| sentinel_config.node_url(node))
+                .collect::<Vec<_>>();
+            let node_info = redis::sentinel::SentinelNodeConnectionInfo {
+                tls_mode: config.tls.as_ref().map(|_| redis::TlsMode::Secure),
+                redis_connection_info: Some(RedisConnectionInfo {
+                    db: config.db.unwrap_or_default() as i64,
+                    username: config.username.clone(),
+                    password: config.password.clone(),
+                    ..Default::default()
+                }),
+            };
+            let mut sentinel = redis::sentinel::Sentinel::build(sentinel_urls)?;
+            let master = futures::executor::block_on(
+                sentinel.async_master_for(service_name, Some(&node_info)),
+            )?;
+            master.get_connection_info().clone()
+        }
+        RedisTopology::Cluster { .. } => {
+            return Err(anyhow::anyhow!(
+                "redis::RedisPool不支持集群部署，请使用redisfred::init_redis_pool_with_config"
+            ))
+        }
+    };
+    let client = match &config.tls {
+        Some(tls) => Client::build_with_tls(client_info, load_tls_certificates(tls)?)?,
+        None => Client::open(client_info)?,
+    };
+    let connection =
+        futures::executor::block_on(async { client.get_multiplexed_async_connection().await })?;
+    // 哨兵或集群部署未配置redlock.nodes时不创建，使用redlock时再返回错误
+    let redlock = new_redlock(config.redlock_nodes());
+    info!("connect to redis successfully");
+    Ok(REDISPOOL.get_or_init(|| RedisPool {
+        client,
+        connection,
+        redlock,
+    }))
+}
+
+fn load_tls_certificates(tls: &RedisTlsConfig) -> anyhow::Result<redis::TlsCertificates> {
+    let client_tls = match (&tls.client_cert, &tls.client_key) {
+        (Some(client_cert), Some(client_key)) => Some(redis::ClientTlsConfig {
+            client_cert: std::fs::read(client_cert)?,
+            client_key: std::fs::read(client_key)?,
+        }),
+        (None, None) => None,
+        _ => return Err(anyhow::anyhow!("client_cert和client_key需要同时配置")),
+    };
+    let root_cert = match &tls.ca_cert {
+        Some(ca_cert) => Some(std::fs::read(ca_cert)?),
+        None => None,
+    };
+    Ok(redis::TlsCertificates {
+        client_tls,
+        root_cert,
+    })
+}
+
 pub fn get_redis_pool() -> &'static RedisPool {
     REDISPOOL.get().unwrap()
 }
 
+pub fn try_get_redis_pool() -> Option<&'static RedisPool> {
+    REDISPOOL.get()
+}
+
 // const REDIS_KEY_PREFIX: &str = "KvCache";
 
 pub async fn get_kv_cache(key: &String) -> anyhow::Result<String> {
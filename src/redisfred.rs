@@ -1,31 +1,45 @@
+use crate::redisconfig::{new_redlock, RedisNode, RedisPoolConfig, RedisTlsConfig, RedisTopology};
 use fred::types::Expiration::EX;
 use fred::{
     prelude::*,
-    types::{PerformanceConfig, RedisConfig},
+    types::{
+        ClusterDiscoveryPolicy, MultipleKeys, MultipleValues, PerformanceConfig, RedisConfig,
+        Server, TlsConfig, TlsConnector,
+    },
 };
 use redlock::RedLock;
-use std::sync::OnceLock;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 use tracing::{debug, info};
 
 #[derive(Clone)]
 pub struct RedisCachePool {
     pub pool: RedisPool,
-    pub redlock: RedLock,
+    pub redlock: RedLock, // 同步阻塞的实现，异步代码中使用lock::DistributedLock
+}
+
+impl RedisCachePool {
+    /// 哨兵或集群部署没有在redlock.nodes中配置相互独立的节点时返回错误
+    pub fn redlock(&self) -> anyhow::Result<&RedLock> {
+        if self.redlock.servers.is_empty() {
+            return Err(anyhow::anyhow!(
+                "哨兵或集群部署需要在redlock.nodes中配置相互独立的redis节点"
+            ));
+        }
+        Ok(&self.redlock)
+    }
 }
 
 static REDISPOOL: OnceLock<RedisCachePool> = OnceLock::<RedisCachePool>::new();
+// 保证并发初始化时只连接一次
+static REDISPOOL_INIT: Mutex<()> = Mutex::new(());
 
 /// redis url format
 /// - `{redis|rediss}://[<username>][:<password>@]<hostname>[:port][/<db>]`
+/// - 哨兵和集群使用`redis-sentinel://`和`redis-cluster://`，或使用init_redis_pool_with_config
 pub fn init_redis_pool(redis_url: String) -> &'static RedisCachePool {
+    let _init = REDISPOOL_INIT.lock().unwrap_or_else(|e| e.into_inner());
     REDISPOOL.get_or_init(|| {
-        // let redis_url = format!(
-        //     "redis://default:{}@{}:{}",
-        //     redis_pass.clone(),
-        //     redis_host.clone(),
-        //     redis_port.clone()
-        // );
-
         // let redis_config = RedisConfig {
         //     server: ServerConfig::new_centralized(&redis_host, redis_port),
         //     password: Some(redis_pass),
@@ -33,75 +47,297 @@ pub fn init_redis_pool(redis_url: String) -> &'static RedisCachePool {
         // };
 
         let redis_config = RedisConfig::from_url(&redis_url).unwrap();
-        let performance = PerformanceConfig {
-            default_command_timeout: std::time::Duration::from_millis(1000 * 3),
-            ..Default::default()
-        };
-        let policy = ReconnectPolicy::new_linear(5, 1000 * 5, 100);
-        let connection_config = ConnectionConfig::default();
-
-        debug!("redis config: {:?}.", &redis_config);
-        let redis_pool = RedisPool::new(
-            redis_config,
-            Some(performance),
-            Some(connection_config),
-            Some(policy),
-            5,
-        )
-        .unwrap();
-        let _join_handler = redis_pool.connect();
-        futures::executor::block_on(async {
-            redis_pool.wait_for_connect().await.unwrap();
-        });
-        info!("connect to redis successfully");
+        // 哨兵或集群的url不创建redlock，使用redlock时返回错误
+        let redlock = new_redlock(Ok(vec![redis_url.clone()]));
+        connect_pool(redis_config, Duration::from_millis(1000 * 3), 5, redlock).unwrap()
+    })
+}
 
-        let redlock = redlock::RedLock::new(vec![redis_url.clone()]);
-        RedisCachePool {
-            pool: redis_pool,
-            redlock: redlock,
-        }
+/// 按配置连接单机、哨兵或集群部署的redis
+pub fn init_redis_pool_with_config(
+    config: RedisPoolConfig,
+) -> anyhow::Result<&'static RedisCachePool> {
+    let _init = REDISPOOL_INIT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pool) = REDISPOOL.get() {
+        return Ok(pool);
+    }
+    let redis_config = to_fred_config(&config)?;
+    // 哨兵或集群部署未配置redlock.nodes时不创建，使用redlock时再返回错误
+    let redlock = new_redlock(config.redlock_nodes());
+    let pool = connect_pool(
+        redis_config,
+        Duration::from_millis(config.command_timeout_ms),
+        config.pool_size,
+        redlock,
+    )?;
+    Ok(REDISPOOL.get_or_init(|| pool))
+}
+
+fn connect_pool(
+    redis_config: RedisConfig,
+    command_timeout: Duration,
+    pool_size: usize,
+    redlock: RedLock,
+) -> anyhow::Result<RedisCachePool> {
+    let performance = PerformanceConfig {
+        default_command_timeout: command_timeout,
+        ..Default::default()
+    };
+    let policy = ReconnectPolicy::new_linear(5, 1000 * 5, 100);
+    let connection_config = ConnectionConfig::default();
+
+    debug!("redis config: {:?}.", &redis_config);
+    let redis_pool = RedisPool::new(
+        redis_config,
+        Some(performance),
+        Some(connection_config),
+        Some(policy),
+        pool_size,
+    )?;
+    let _join_handler = redis_pool.connect();
+    futures::executor::block_on(async { redis_pool.wait_for_connect().await })?;
+    info!("connect to redis successfully");
+
+    Ok(RedisCachePool {
+        pool: redis_pool,
+        redlock,
     })
 }
 
+fn to_fred_config(config: &RedisPoolConfig) -> anyhow::Result<RedisConfig> {
+    let servers = |nodes: &Vec<RedisNode>| {
+        nodes
+            .iter()
+            .map(|node| Server::new(node.host.as_str(), node.port))
+            .collect::<Vec<_>>()
+    };
+    let server = match &config.topology {
+        RedisTopology::Centralized { node } => ServerConfig::Centralized {
+            server: Server::new(node.host.as_str(), node.port),
+        },
+        RedisTopology::Sentinel {
+            nodes,
+            service_name,
+            sentinel_username,
+            sentinel_password,
+        } => ServerConfig::Sentinel {
+            hosts: servers(nodes),
+            service_name: service_name.clone(),
+            username: sentinel_username.clone().or(config.username.clone()),
+            password: sentinel_password.clone().or(config.password.clone()),
+        },
+        RedisTopology::Cluster { nodes } => ServerConfig::Clustered {
+            hosts: servers(nodes),
+            policy: ClusterDiscoveryPolicy::default(),
+        },
+    };
+    let tls = match &config.tls {
+        Some(tls) => Some(to_fred_tls(tls)?),
+        None => None,
+    };
+    Ok(RedisConfig {
+        server,
+        username: config.username.clone(),
+        password: config.password.clone(),
+        database: config.db,
+        tls,
+        ..Default::default()
+    })
+}
+
+fn to_fred_tls(tls: &RedisTlsConfig) -> anyhow::Result<TlsConfig> {
+    use fred::rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+    use fred::rustls::{crypto::ring, ClientConfig, RootCertStore};
+
+    let mut roots = RootCertStore::empty();
+    match &tls.ca_cert {
+        Some(ca_cert) => {
+            for cert in CertificateDer::pem_file_iter(ca_cert)? {
+                roots.add(cert?)?;
+            }
+        }
+        None => {
+            for cert in fred::rustls_native_certs::load_native_certs()? {
+                roots.add(cert)?;
+            }
+        }
+    }
+    let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots);
+    let client_config = match (&tls.client_cert, &tls.client_key) {
+        (Some(client_cert), Some(client_key)) => {
+            let certs =
+                CertificateDer::pem_file_iter(client_cert)?.collect::<Result<Vec<_>, _>>()?;
+            builder.with_client_auth_cert(certs, PrivateKeyDer::from_pem_file(client_key)?)?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err(anyhow::anyhow!("client_cert和client_key需要同时配置")),
+    };
+    Ok(TlsConnector::from(client_config).into())
+}
+
 pub fn get_redis_pool() -> &'static RedisCachePool {
     REDISPOOL.get().unwrap()
 }
 
+pub fn try_get_redis_pool() -> Option<&'static RedisCachePool> {
+    REDISPOOL.get()
+}
+
 pub fn get_redis_client() -> &'static fred::clients::RedisClient {
     REDISPOOL.get().unwrap().pool.next()
 }
 
+/// 使用与连接池相同的配置创建订阅客户端，断线重连后自动重新订阅
+pub async fn new_subscriber_client() -> anyhow::Result<fred::clients::SubscriberClient> {
+    new_subscriber_client_from(get_redis_client()).await
+}
+
+/// 使用与client相同的配置创建订阅客户端
+pub async fn new_subscriber_client_from(
+    client: &fred::clients::RedisClient,
+) -> anyhow::Result<fred::clients::SubscriberClient> {
+    let mut builder = fred::types::Builder::from_config(client.client_config());
+    builder
+        .set_performance_config(client.perf_config())
+        .set_connection_config(client.connection_config().clone());
+    if let Some(policy) = client.client_reconnect_policy() {
+        builder.set_policy(policy);
+    }
+    let subscriber = builder.build_subscriber_client()?;
+    subscriber.init().await?;
+    let _join_handler = subscriber.manage_subscriptions();
+    Ok(subscriber)
+}
+
 // const REDIS_KEY_PREFIX: &str = "KvCache";
 
 pub async fn get_kv_cache(key: &String) -> anyhow::Result<String> {
     let redis = get_redis_pool();
     // let key = format!("{}:{}", REDIS_KEY_PREFIX, key);
-    let res: String = redis.pool.get(key).await?;
-    Ok(res)
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+    let res: Result<String, RedisError> = redis.pool.get(key).await;
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_redis_command("get", start, &res);
+    Ok(res?)
 }
 
 pub async fn set_kv_cache(key: &String, value: &String, ex: Option<i64>) -> anyhow::Result<()> {
     let redis = get_redis_pool();
     // let key = format!("{}:{}", REDIS_KEY_PREFIX, key);
-    let _res = if ex.is_none() {
-        let res: RedisValue = redis
-            .pool
-            .set(key, value.clone(), None, None, false)
-            .await?;
-        res
-    } else {
-        let res: RedisValue = redis
-            .pool
-            .set(key, value.clone(), Some(EX(ex.unwrap())), None, false)
-            .await?;
-        res
-    };
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+    let res: Result<RedisValue, RedisError> = redis
+        .pool
+        .set(key, value.clone(), ex.map(EX), None, false)
+        .await;
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_redis_command("set", start, &res);
+    res?;
     Ok(())
 }
 
 pub async fn delete_kv_cache(key: &String) -> anyhow::Result<()> {
     let redis = get_redis_pool();
     // let key = format!("{}:{}", REDIS_KEY_PREFIX, key);
-    let _res: String = redis.pool.del(key).await?;
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+    let res: Result<i64, RedisError> = redis.pool.del(key).await;
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_redis_command("del", start, &res);
+    res?;
     Ok(())
 }
+
+/// stream中的一条消息: (消息id, 字段)，用于jobqueue和eventbus
+pub(crate) type StreamEntry = (String, std::collections::HashMap<String, String>);
+
+/// lua脚本开头取redis服务器的毫秒时间now，使用服务器的时间避免多个实例之间的时钟偏差
+/// - `CachedScript::new(concat!(lua_now_millis!(), "return now"))`
+macro_rules! lua_now_millis {
+    () => {
+        "local t = redis.call('TIME')\nlocal now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)\n"
+    };
+}
+pub(crate) use lua_now_millis;
+
+/// 首次使用时SCRIPT LOAD得到sha，之后使用EVALSHA，避免每次执行都发送整个脚本，
+/// redis重启或在集群的其他节点上执行时返回NOSCRIPT，改用EVAL执行并缓存到该节点
+pub(crate) struct CachedScript {
+    lua: &'static str,
+    sha: std::sync::RwLock<Option<String>>,
+}
+
+impl CachedScript {
+    pub(crate) const fn new(lua: &'static str) -> Self {
+        Self {
+            lua,
+            sha: std::sync::RwLock::new(None),
+        }
+    }
+
+    pub(crate) async fn eval<R, K, V>(
+        &self,
+        redis: &RedisPool,
+        keys: K,
+        args: V,
+    ) -> Result<R, RedisError>
+    where
+        R: FromRedis,
+        K: Into<MultipleKeys>,
+        V: TryInto<MultipleValues>,
+        V::Error: Into<RedisError>,
+    {
+        let keys: MultipleKeys = keys.into();
+        let args: MultipleValues = args.try_into().map_err(Into::into)?;
+        let cached = self.sha.read().unwrap().clone();
+        let sha = match cached {
+            Some(sha) => sha,
+            None => {
+                let sha: String = redis.script_load(self.lua).await?;
+                *self.sha.write().unwrap() = Some(sha.clone());
+                sha
+            }
+        };
+        match redis.evalsha(sha, keys.clone(), args.clone()).await {
+            Err(e) if e.details().starts_with("NOSCRIPT") => redis.eval(self.lua, keys, args).await,
+            res => res,
+        }
+    }
+}
+
+/// 创建stream的消费组，消费组已存在时忽略
+pub(crate) async fn ensure_stream_group(
+    redis: &RedisPool,
+    stream: &str,
+    group: &str,
+) -> anyhow::Result<()> {
+    let res: Result<(), RedisError> = redis.xgroup_create(stream, group, "0", true).await;
+    match res {
+        Err(e) if !e.details().contains("BUSYGROUP") => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// 阻塞读取(XREADGROUP BLOCK)使用的单独连接，避免阻塞连接池中的其他命令，
+/// 不再使用时调用quit_in_background关闭
+pub(crate) async fn connect_blocking_client(
+    redis: &RedisPool,
+) -> anyhow::Result<fred::clients::RedisClient> {
+    let client = redis.next().clone_new();
+    let _join_handler = client.connect();
+    client.wait_for_connect().await?;
+    Ok(client)
+}
+
+/// 在Drop等不能await的地方关闭连接，没有tokio运行时时由连接自行断开
+pub(crate) fn quit_in_background<C: ClientLike + Clone + 'static>(client: &C) {
+    let client = client.clone();
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        handle.spawn(async move {
+            let _ = client.quit().await;
+        });
+    }
+}
@@ -1,13 +1,31 @@
-use actix_web::{http::StatusCode, HttpResponse};
-use serde::{Deserialize, Serialize};
+use crate::middleware::current_request_id;
+use actix_web::{http::StatusCode, web::Bytes, HttpResponse};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures::{Stream, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::fmt::Debug;
-use utoipa::ToSchema;
+use std::sync::atomic::{AtomicBool, Ordering};
+use utoipa::{IntoParams, ToSchema};
+
+static HTTP_STATUS_MODE: AtomicBool = AtomicBool::new(false);
+
+// 未知错误返回给客户端的信息，错误详情只记录到日志
+const INTERNAL_ERROR_MESSAGE: &str = "服务器内部错误";
+
+/// 设置为true后，返回的HTTP状态码与Response.code一致，否则始终返回200，仅在JSON的code中体现
+pub fn set_http_status_mode(enabled: bool) {
+    HTTP_STATUS_MODE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_http_status_mode() -> bool {
+    HTTP_STATUS_MODE.load(Ordering::Relaxed)
+}
 
 #[derive(Deserialize, Serialize, Debug, ToSchema)]
 pub struct NoneBodyData {}
 
 /// Client使用的通用格式返回数据类型，message可为String或泛型T
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct ClientRsp {
     pub status: bool,
     pub code: u16,
@@ -15,114 +33,221 @@ pub struct ClientRsp {
 }
 
 /// Client使用的正确返回类型数据格式定义
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct OkData<T: Serialize + Debug> {
     pub status: bool,
     pub code: u16,
     pub message: T,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 /// Client使用的错误返回类型数据格式定义
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct ErrData {
     pub status: bool,
     pub code: u16,
     pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>, // 业务错误码，如"USER_NOT_FOUND"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // 错误的详细信息，如字段校验失败列表
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// 带有HTTP状态码和业务错误码的错误，可通过?直接转换为Response
+#[derive(Clone, Debug)]
+pub struct AppError {
+    pub code: StatusCode,
+    pub error_code: Option<String>,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+}
+
+impl AppError {
+    pub fn new(code: StatusCode, error_code: &str, message: &str) -> Self {
+        Self {
+            code,
+            error_code: Some(error_code.to_string()),
+            message: message.to_string(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
 }
 
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.error_code {
+            Some(error_code) => write!(f, "{}: {}", error_code, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// 保存在HttpResponse的extensions中的Response.code，HTTP状态码始终为200时中间件通过它获取真实状态
+#[derive(Clone, Copy, Debug)]
+pub struct EnvelopeCode(pub u16);
+
 /// 服务端使用的回复响应泛型数据类型
 #[derive(Debug)]
 pub struct Response<T: Serialize + Debug> {
     pub code: StatusCode,
     pub message: String,
     pub data: Option<T>,
+    pub error_code: Option<String>,
+    pub details: Option<serde_json::Value>,
+    pub request_id: Option<String>,
 }
 
 impl<T: Serialize + Debug> Response<T> {
-    #[allow(dead_code)]
-    pub fn success(data: T) -> Self {
+    /// 之后可能继续增加字段，使用构造函数和with_*方法创建，不要直接使用结构体字面量
+    pub fn new(code: StatusCode, message: &str, data: Option<T>) -> Self {
         Self {
-            code: StatusCode::OK,
-            message: "".to_string(),
-            data: Some(data),
+            code,
+            message: message.to_string(),
+            data,
+            error_code: None,
+            details: None,
+            request_id: None,
         }
     }
 
+    #[allow(dead_code)]
+    pub fn success(data: T) -> Self {
+        Self::new(StatusCode::OK, "", Some(data))
+    }
+
     #[allow(dead_code)]
     pub fn internal_error(message: &str) -> Self {
-        Self {
-            code: StatusCode::INTERNAL_SERVER_ERROR,
-            message: message.to_string(),
-            data: None,
-        }
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, message, None)
     }
 
     #[allow(dead_code)]
     pub fn bad_request(message: &str) -> Self {
-        Self {
-            code: StatusCode::BAD_REQUEST,
-            message: message.to_string(),
-            data: None,
-        }
+        Self::new(StatusCode::BAD_REQUEST, message, None)
     }
 
     #[allow(dead_code)]
     pub fn forbidden(message: &str) -> Self {
-        Self {
-            code: StatusCode::FORBIDDEN,
-            message: message.to_string(),
-            data: None,
-        }
+        Self::new(StatusCode::FORBIDDEN, message, None)
     }
 
     #[allow(dead_code)]
     pub fn not_acceptable(message: &str) -> Self {
-        Self {
-            code: StatusCode::NOT_ACCEPTABLE,
-            message: message.to_string(),
-            data: None,
-        }
+        Self::new(StatusCode::NOT_ACCEPTABLE, message, None)
     }
 
     #[allow(dead_code)]
     pub fn unauthorized(message: &str) -> Self {
-        Self {
-            code: StatusCode::UNAUTHORIZED,
-            message: message.to_string(),
-            data: None,
-        }
+        Self::new(StatusCode::UNAUTHORIZED, message, None)
     }
 
     #[allow(dead_code)]
     pub fn nofound(message: &str) -> Self {
-        Self {
-            code: StatusCode::NOT_FOUND,
-            message: message.to_string(),
-            data: None,
+        Self::new(StatusCode::NOT_FOUND, message, None)
+    }
+
+    pub fn error(code: StatusCode, message: &str) -> Self {
+        Self::new(code, message, None)
+    }
+
+    pub fn with_error_code(mut self, error_code: &str) -> Self {
+        self.error_code = Some(error_code.to_string());
+        self
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: &str) -> Self {
+        self.request_id = Some(request_id.to_string());
+        self
+    }
+
+    fn http_response_builder(&self) -> actix_web::HttpResponseBuilder {
+        let mut builder = if is_http_status_mode() {
+            HttpResponse::build(self.code)
+        } else {
+            HttpResponse::Ok()
+        };
+        builder
+            .extensions_mut()
+            .insert(EnvelopeCode(self.code.as_u16()));
+        builder
+    }
+
+    fn err_data(&self) -> ErrData {
+        ErrData {
+            status: false,
+            code: self.code.as_u16(),
+            message: self.message.clone(),
+            error_code: self.error_code.clone(),
+            details: self.details.clone(),
+            request_id: self.request_id.clone().or_else(current_request_id),
         }
     }
 
     pub fn finished(&self) -> HttpResponse {
         if self.code.is_success() {
-            HttpResponse::Ok().json(serde_json::json!(OkData {
+            self.http_response_builder().json(serde_json::json!(OkData {
                 status: true,
                 code: self.code.as_u16(),
                 message: self.data.as_ref().unwrap(),
+                request_id: self.request_id.clone().or_else(current_request_id),
             }))
         } else {
-            HttpResponse::Ok().json(serde_json::json!(ErrData {
-                status: false,
-                code: self.code.as_u16(),
-                message: self.message.clone(),
-            }))
+            self.http_response_builder()
+                .json(serde_json::json!(self.err_data()))
         }
     }
 
+    /// 与finished相同，但始终以code作为HTTP状态码，不受set_http_status_mode影响，
+    /// 用于探针、限流等调用方只看状态码的场景
+    pub fn finished_with_status(&self) -> HttpResponse {
+        let mut resp = self.finished();
+        *resp.status_mut() = self.code;
+        resp
+    }
+
     pub fn with_response(&self, resp: HttpResponse) -> HttpResponse {
         resp
     }
 }
 
+impl<T: Serialize + Debug> From<AppError> for Response<T> {
+    fn from(e: AppError) -> Self {
+        Self {
+            error_code: e.error_code,
+            details: e.details,
+            ..Self::new(e.code, &e.message, None)
+        }
+    }
+}
+
+/// anyhow::Error中包装的是AppError时保留其状态码和错误码，否则作为500错误，
+/// 错误详情可能包含SQL、地址等内部信息，只记录到日志，返回给客户端通用的错误信息
+impl<T: Serialize + Debug> From<anyhow::Error> for Response<T> {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<AppError>() {
+            Ok(app_error) => app_error.into(),
+            Err(e) => {
+                tracing::error!("internal error: {:?}", e);
+                Self::internal_error(INTERNAL_ERROR_MESSAGE)
+            }
+        }
+    }
+}
+
 impl<T: Serialize + Debug> std::fmt::Display for Response<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{{ code: {}, message: {} }}", self.code, self.message)
@@ -131,19 +256,274 @@ impl<T: Serialize + Debug> std::fmt::Display for Response<T> {
 
 impl<T: Serialize + Debug> actix_web::error::ResponseError for Response<T> {
     fn status_code(&self) -> StatusCode {
-        self.code.clone()
+        self.code
     }
 
     fn error_response(&self) -> HttpResponse {
-        // HttpResponse::build(self.code).json(serde_json::json!(ErrData {
-        //     status: false,
-        //     code: self.code.as_u16(),
-        //     message: self.message.clone(),
-        // }))
-        HttpResponse::Ok().json(serde_json::json!(ErrData {
-            status: false,
-            code: self.code.as_u16(),
-            message: self.message.clone(),
-        }))
+        self.http_response_builder()
+            .json(serde_json::json!(self.err_data()))
+    }
+}
+
+pub const DEFAULT_PAGE_SIZE: u64 = 20;
+pub const MAX_PAGE_SIZE: u64 = 1000;
+
+/// 列表接口的分页请求参数，用法: `query: web::Query<PageQuery>`
+/// - 页码分页: `?page=2&page_size=20`，page从1开始
+/// - 游标分页: `?cursor=xxx&page_size=20`，cursor为上一页返回的next_cursor
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    pub page: Option<u64>,
+    pub page_size: Option<u64>,
+    pub cursor: Option<String>,
+}
+
+impl PageQuery {
+    pub fn page(&self) -> u64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn page_size(&self) -> u64 {
+        self.page_size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// page由客户端传入，可能非常大，使用饱和乘法避免溢出
+    pub fn offset(&self) -> u64 {
+        (self.page() - 1).saturating_mul(self.page_size())
+    }
+
+    /// 解析cursor参数，未传cursor时为None，表示从第一页开始
+    pub fn cursor<C: DeserializeOwned>(&self) -> anyhow::Result<Option<C>> {
+        match &self.cursor {
+            Some(cursor) if !cursor.is_empty() => Ok(Some(decode_cursor(cursor)?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// 将游标数据(如最后一条记录的id和排序字段)编码为URL安全的字符串
+pub fn encode_cursor<C: Serialize>(cursor: &C) -> anyhow::Result<String> {
+    let data = serde_json::to_vec(cursor)?;
+    Ok(URL_SAFE_NO_PAD.encode(data))
+}
+
+pub fn decode_cursor<C: DeserializeOwned>(cursor: &str) -> anyhow::Result<C> {
+    let data = URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|e| anyhow::anyhow!("cursor格式有误: {:?}", e))?;
+    Ok(serde_json::from_slice::<C>(&data)?)
+}
+
+/// 列表接口的分页返回数据，游标分页时total可能为None
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    pub page: u64,
+    pub page_size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// 页码分页，items为offset/limit查询出的当前页数据
+    pub fn new(items: Vec<T>, total: u64, query: &PageQuery) -> Self {
+        Self {
+            items,
+            total: Some(total),
+            page: query.page(),
+            page_size: query.page_size(),
+            next_cursor: None,
+        }
+    }
+
+    /// 游标分页，items需多查询一条(page_size + 1)用于判断是否还有下一页，
+    /// cursor_of根据当前页最后一条数据生成下一页的游标
+    pub fn from_cursor<C: Serialize>(
+        mut items: Vec<T>,
+        query: &PageQuery,
+        cursor_of: impl Fn(&T) -> C,
+    ) -> anyhow::Result<Self> {
+        let page_size = query.page_size();
+        let next_cursor = if items.len() as u64 > page_size {
+            items.truncate(page_size as usize);
+            match items.last() {
+                Some(last) => Some(encode_cursor(&cursor_of(last))?),
+                None => None,
+            }
+        } else {
+            None
+        };
+        Ok(Self {
+            items,
+            total: None,
+            page: query.page(),
+            page_size,
+            next_cursor,
+        })
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            page: self.page,
+            page_size: self.page_size,
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+/// 大批量导出使用的流式返回，每条数据一行JSON(application/x-ndjson)，
+/// 出错时写入一行ErrData后结束
+pub fn ndjson_stream<T, S>(stream: S) -> HttpResponse
+where
+    T: Serialize + 'static,
+    S: Stream<Item = anyhow::Result<T>> + 'static,
+{
+    let body = stream_lines(stream, |data| {
+        let mut line = serde_json::to_vec(&data).unwrap_or_default();
+        line.push(b'\n');
+        line
+    });
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(body)
+}
+
+/// Server-Sent Events方式的流式返回，每条数据为一个message事件，出错时发送error事件后结束
+pub fn sse_stream<T, S>(stream: S) -> HttpResponse
+where
+    T: Serialize + 'static,
+    S: Stream<Item = anyhow::Result<T>> + 'static,
+{
+    let body = stream_lines(stream, |data| {
+        let (event, data) = match data {
+            StreamItem::Data(data) => ("message", data),
+            StreamItem::Error(data) => ("error", data),
+        };
+        format!("event: {}\ndata: {}\n\n", event, data).into_bytes()
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((actix_web::http::header::CACHE_CONTROL, "no-cache"))
+        .streaming(body)
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum StreamItem {
+    Data(serde_json::Value),
+    Error(serde_json::Value),
+}
+
+fn stream_lines<T, S>(
+    stream: S,
+    format: impl Fn(StreamItem) -> Vec<u8> + 'static,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>>
+where
+    T: Serialize + 'static,
+    S: Stream<Item = anyhow::Result<T>> + 'static,
+{
+    stream.scan(false, move |failed, item| {
+        if *failed {
+            return futures::future::ready(None);
+        }
+        let item = match item.and_then(|data| Ok(serde_json::to_value(&data)?)) {
+            Ok(data) => StreamItem::Data(data),
+            Err(e) => {
+                tracing::error!("stream response error: {:?}", e);
+                *failed = true;
+                StreamItem::Error(serde_json::json!(Response::<NoneBodyData>::internal_error(
+                    INTERNAL_ERROR_MESSAGE
+                )
+                .err_data()))
+            }
+        };
+        futures::future::ready(Some(Ok(Bytes::from(format(item)))))
+    })
+}
+
+#[cfg(test)]
+mod response_tests {
+    use super::*;
+
+    #[test]
+    fn anyhow_into_response_test() {
+        let e = anyhow::Error::new(AppError::new(
+            StatusCode::NOT_FOUND,
+            "USER_NOT_FOUND",
+            "用户不存在",
+        ));
+        let rsp: Response<NoneBodyData> = e.into();
+        assert_eq!(rsp.code, StatusCode::NOT_FOUND);
+        assert_eq!(rsp.error_code.as_deref(), Some("USER_NOT_FOUND"));
+
+        let rsp: Response<NoneBodyData> = anyhow::anyhow!("db error").into();
+        assert_eq!(rsp.code, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(rsp.message, INTERNAL_ERROR_MESSAGE);
+        assert!(rsp.error_code.is_none());
+    }
+
+    #[actix_web::test]
+    async fn success_request_id_test() {
+        use actix_web::{test, web, App};
+        let app = test::init_service(App::new().wrap(crate::middleware::RequestLog).route(
+            "/ok",
+            web::get().to(|| async { Response::success(1).finished() }),
+        ))
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/ok")
+            .insert_header(("X-Request-Id", "req-1"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["request_id"], "req-1");
+        assert_eq!(body["message"], 1);
+    }
+
+    #[test]
+    fn finished_with_status_test() {
+        let rsp = Response::<NoneBodyData>::error(StatusCode::SERVICE_UNAVAILABLE, "不可用");
+        assert_eq!(rsp.finished().status(), StatusCode::OK);
+        assert_eq!(
+            rsp.finished_with_status().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[test]
+    fn page_offset_test() {
+        let query = PageQuery {
+            page: Some(u64::MAX),
+            page_size: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(query.offset(), u64::MAX);
+        let query = PageQuery {
+            page: Some(3),
+            page_size: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(query.offset(), 20);
+    }
+
+    #[test]
+    fn cursor_page_test() {
+        let query = PageQuery {
+            page_size: Some(2),
+            ..Default::default()
+        };
+        let page = Page::from_cursor(vec![1u64, 2, 3], &query, |id| *id).unwrap();
+        assert_eq!(page.items, vec![1, 2]);
+        let query = PageQuery {
+            cursor: page.next_cursor,
+            ..Default::default()
+        };
+        assert_eq!(query.cursor::<u64>().unwrap(), Some(2));
     }
 }
@@ -25,6 +25,14 @@ fn from_east_8_to_utc(
     east_8.with_timezone(&chrono::Utc)
 }
 
+/// 当前的毫秒时间戳，系统时间早于1970年时返回0
+pub fn get_now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
 pub fn get_current_time() -> chrono::DateTime<chrono::Utc> {
     chrono::Utc::now()
 }
@@ -0,0 +1,370 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, DeriveInput, Ident, ItemFn, LitStr, Token, Type};
+
+const ROUTE_MACROS: [&str; 9] = [
+    "get", "post", "put", "delete", "patch", "head", "options", "trace", "connect",
+];
+
+#[derive(Default)]
+struct PermissionArgs {
+    page: Option<LitStr>,
+    action: Option<LitStr>,
+    route: Option<LitStr>,
+    prefix: Option<LitStr>,
+    whitelist: bool,
+}
+
+/// 为actix路由注册权限，由init_pmap收集到PermissionMap中，需写在actix路由宏的上方
+/// - `#[permission(page = "order", action = "read")]`
+/// - `#[permission(whitelist)]`: 加入白名单
+/// - `prefix = "/api/v1"`: 路由所在scope的前缀
+/// - `route = "GET /api/v1/order"`: 不使用路由宏时直接指定
+#[proc_macro_attribute]
+pub fn permission(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = PermissionArgs::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("whitelist") {
+            args.whitelist = true;
+        } else if meta.path.is_ident("page") {
+            args.page = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("action") {
+            args.action = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("route") {
+            args.route = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("prefix") {
+            args.prefix = Some(meta.value()?.parse()?);
+        } else {
+            return Err(meta.error("unsupported permission property"));
+        }
+        Ok(())
+    });
+    parse_macro_input!(attr with parser);
+    let item_fn = parse_macro_input!(item as ItemFn);
+
+    match expand(&args, &item_fn) {
+        Ok(submits) => quote! {
+            #item_fn
+            #(#submits)*
+        }
+        .into(),
+        Err(e) => {
+            let e = e.to_compile_error();
+            quote! {
+                #item_fn
+                #e
+            }
+            .into()
+        }
+    }
+}
+
+fn expand(args: &PermissionArgs, item_fn: &ItemFn) -> syn::Result<Vec<proc_macro2::TokenStream>> {
+    let (page, action) = if args.whitelist {
+        (String::new(), String::new())
+    } else {
+        match (&args.page, &args.action) {
+            (Some(page), Some(action)) => (page.value(), action.value()),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &item_fn.sig.ident,
+                    "permission requires page and action, or whitelist",
+                ))
+            }
+        }
+    };
+
+    let routes = match &args.route {
+        Some(route) => vec![route.value()],
+        None => {
+            let prefix = args
+                .prefix
+                .as_ref()
+                .map(|prefix| prefix.value().trim_end_matches('/').to_string())
+                .unwrap_or_default();
+            let routes = find_routes(item_fn)?
+                .into_iter()
+                .map(|(method, path)| {
+                    route_regex(&method, &prefix, &path)
+                        .map_err(|e| syn::Error::new_spanned(&item_fn.sig.ident, e))
+                })
+                .collect::<syn::Result<Vec<String>>>()?;
+            if routes.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    &item_fn.sig.ident,
+                    "no actix route macro found below #[permission], or set route = \"METHOD /path\"",
+                ));
+            }
+            routes
+        }
+    };
+
+    let whitelist = args.whitelist;
+    Ok(routes
+        .into_iter()
+        .map(|route| {
+            quote! {
+                ::webbase::inventory::submit! {
+                    ::webbase::permission::PermissionRoute {
+                        route: #route,
+                        page: #page,
+                        action: #action,
+                        whitelist: #whitelist,
+                    }
+                }
+            }
+        })
+        .collect())
+}
+
+/// 为sea-orm的Model生成审计字段的ActiveModelBehavior，需与DeriveEntityModel写在同一个Model上，
+/// 不能再手写`impl ActiveModelBehavior for ActiveModel {}`
+/// - created_at、created_by: insert时填充
+/// - updated_at、updated_by: insert和update时填充
+/// - deleted_at: 必须为Option，为Entity实现webbase::db::SoftDelete
+///
+/// 时间字段使用webbase::db::AuditTime，操作者字段使用webbase::db::AuditActor，
+/// 没有当前操作者时不修改created_by和updated_by
+#[proc_macro_derive(Audited)]
+pub fn derive_audited(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    match expand_audited(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_audited(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Audited only supports structs with named fields",
+            ))
+        }
+    };
+    let field = |name: &str| -> Option<(Ident, Type)> {
+        fields
+            .iter()
+            .find(|field| field.ident.as_ref().is_some_and(|ident| ident == name))
+            .map(|field| (field.ident.clone().unwrap(), field.ty.clone()))
+    };
+
+    let mut on_insert = Vec::new();
+    let mut on_save = Vec::new();
+    for (name, actor, insert_only) in [
+        ("created_at", false, true),
+        ("created_by", true, true),
+        ("updated_at", false, false),
+        ("updated_by", true, false),
+    ] {
+        let Some((ident, ty)) = field(name) else {
+            continue;
+        };
+        let assign = if actor {
+            quote! {
+                if let Some(actor) = &actor {
+                    self.#ident = ::sea_orm::ActiveValue::Set(
+                        <#ty as ::webbase::db::AuditActor>::audit_actor(actor),
+                    );
+                }
+            }
+        } else {
+            quote! {
+                self.#ident = ::sea_orm::ActiveValue::Set(
+                    <#ty as ::webbase::db::AuditTime>::audit_time(&now),
+                );
+            }
+        };
+        if insert_only {
+            on_insert.push(assign);
+        } else {
+            on_save.push(assign);
+        }
+    }
+    if on_insert.is_empty() && on_save.is_empty() && field("deleted_at").is_none() {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Audited requires created_at, updated_at, created_by, updated_by or deleted_at",
+        ));
+    }
+
+    let soft_delete = match field("deleted_at") {
+        Some((_, ty)) => {
+            let is_option = matches!(&ty, Type::Path(path)
+                if path.path.segments.last().is_some_and(|segment| segment.ident == "Option"));
+            if !is_option {
+                return Err(syn::Error::new_spanned(ty, "deleted_at must be Option"));
+            }
+            quote! {
+                impl ::webbase::db::SoftDelete for Entity {
+                    fn deleted_at_column() -> Column {
+                        Column::DeletedAt
+                    }
+
+                    fn deleted_at_value() -> ::sea_orm::Value {
+                        let (now, _) = ::webbase::db::audit_context();
+                        ::sea_orm::Value::from(<#ty as ::webbase::db::AuditTime>::audit_time(&now))
+                    }
+                }
+            }
+        }
+        None => quote! {},
+    };
+
+    Ok(quote! {
+        #[::webbase::async_trait::async_trait]
+        impl ::sea_orm::ActiveModelBehavior for ActiveModel {
+            async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, ::sea_orm::DbErr>
+            where
+                C: ::sea_orm::ConnectionTrait,
+            {
+                #[allow(unused_variables)]
+                let (now, actor) = ::webbase::db::audit_context();
+                if insert {
+                    #(#on_insert)*
+                }
+                #(#on_save)*
+                Ok(self)
+            }
+        }
+
+        #soft_delete
+    })
+}
+
+struct RouteArgs {
+    path: LitStr,
+    methods: Vec<String>,
+}
+
+impl Parse for RouteArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path: LitStr = input.parse()?;
+        let mut methods = Vec::new();
+        while input.parse::<Token![,]>().is_ok() {
+            if input.is_empty() {
+                break;
+            }
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let value: syn::Expr = input.parse()?;
+            if key == "method" {
+                if let syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(method),
+                    ..
+                }) = value
+                {
+                    methods.push(method.value().to_uppercase());
+                }
+            }
+        }
+        Ok(Self { path, methods })
+    }
+}
+
+/// 查找actix的路由宏，返回(METHOD, path)列表
+fn find_routes(item_fn: &ItemFn) -> syn::Result<Vec<(String, String)>> {
+    let mut routes = Vec::new();
+    for attr in item_fn.attrs.iter() {
+        let name = match attr.path().segments.last() {
+            Some(segment) => segment.ident.to_string(),
+            None => continue,
+        };
+        if ROUTE_MACROS.contains(&name.as_str()) {
+            let args: RouteArgs = attr.parse_args()?;
+            routes.push((name.to_uppercase(), args.path.value()));
+        } else if name == "route" {
+            let args: RouteArgs = attr.parse_args()?;
+            for method in args.methods {
+                routes.push((method, args.path.value()));
+            }
+        }
+    }
+    Ok(routes)
+}
+
+/// 将actix的路径模板转为匹配"METHOD path"的正则表达式，首尾锚定，避免匹配到以该路径开头的其他路径
+/// - `/order` -> `^GET /order$`
+/// - `/order/{id}` -> `^GET /order/[^/]+$`
+/// - `/file/{name:.*}` -> `^GET /file/.*$`
+fn route_regex(method: &str, prefix: &str, path: &str) -> Result<String, String> {
+    let path = format!("{}{}", prefix, path);
+    let mut regex = String::new();
+    let mut rest = path.as_str();
+    while let Some(start) = rest.find('{') {
+        regex.push_str(&escape(&rest[..start]));
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => return Err(format!("unclosed `{{` in route path: {}", path)),
+        };
+        let param = &rest[start + 1..end];
+        match param.split_once(':') {
+            Some((_, pattern)) => regex.push_str(pattern),
+            None => regex.push_str("[^/]+"),
+        }
+        rest = &rest[end + 1..];
+    }
+    regex.push_str(&escape(rest));
+    Ok(format!("^{} {}$", method, regex))
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod macros_tests {
+    use super::*;
+
+    #[test]
+    fn route_regex_test() {
+        assert_eq!(
+            route_regex("GET", "/api/v1", "/order").unwrap(),
+            "^GET /api/v1/order$"
+        );
+        assert_eq!(
+            route_regex("GET", "", "/order/{id}/item.json").unwrap(),
+            "^GET /order/[^/]+/item\\.json$"
+        );
+        assert_eq!(
+            route_regex("GET", "", "/file/{name:.*}").unwrap(),
+            "^GET /file/.*$"
+        );
+        assert!(route_regex("GET", "", "/order/{id").is_err());
+    }
+
+    #[test]
+    fn expand_test() {
+        let item_fn: ItemFn = syn::parse_quote! {
+            #[get("/order/{id}")]
+            async fn get_order() {}
+        };
+        let args = PermissionArgs {
+            page: Some(syn::parse_quote!("order")),
+            action: Some(syn::parse_quote!("read")),
+            prefix: Some(syn::parse_quote!("/api/v1/")),
+            ..Default::default()
+        };
+        let submits = expand(&args, &item_fn).unwrap();
+        assert_eq!(submits.len(), 1);
+        assert!(submits[0]
+            .to_string()
+            .contains("\"^GET /api/v1/order/[^/]+$\""));
+
+        let args = PermissionArgs::default();
+        assert!(expand(&args, &item_fn).is_err());
+    }
+}